@@ -0,0 +1,77 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use rust_opse::{HGD, HmacSha256AesCtr, OPE, PRNG, TapeCache, TapeGenerator, ValueRange};
+
+fn bench_encrypt (c: &mut Criterion) {
+    let ope: OPE = OPE::new("key", &ValueRange::new(0_f64, 1_000_000_f64), &ValueRange::new(0_f64, 1_000_000_000_f64));
+
+    c.bench_function("encrypt", |b| b.iter(|| ope.encrypt(black_box(&123_456_f64))));
+}
+
+fn bench_encrypt_batch (c: &mut Criterion) {
+    // Nearby plaintexts, sharing most of their partition points
+    let ope: OPE = OPE::new("key", &ValueRange::new(0_f64, 1_000_000_f64), &ValueRange::new(0_f64, 1_000_000_000_f64));
+    let plaintexts: Vec<f64> = (0..1000).map(|index| (123_456 + index) as f64).collect();
+
+    c.bench_function("encrypt_batch", |b| b.iter(|| ope.encrypt_batch(black_box(&plaintexts))));
+    c.bench_function("encrypt_batch_cached", |b| b.iter(|| {
+        let mut cache: TapeCache = TapeCache::new();
+        ope.encrypt_batch_cached(black_box(&plaintexts), &mut cache)
+    }));
+}
+
+fn bench_encrypt_sizes (c: &mut Criterion) {
+    // out_range of 2^bits values, with twice as many bits as in_range. Up to
+    // 2^53 values, ciphertexts are f64 values, encrypt_u128 is needed beyond.
+    let mut group = c.benchmark_group("encrypt_sizes");
+
+    for bits in [10_i32, 40_i32] {
+        let in_end: f64 = 2_f64.powi(bits / 2) - 1_f64;
+        let ope: OPE = OPE::new("key", &ValueRange::new(0_f64, in_end), &ValueRange::new(0_f64, 2_f64.powi(bits) - 1_f64));
+        let plaintext: f64 = (in_end / 3_f64).floor();
+
+        group.bench_with_input(BenchmarkId::new("encrypt", bits), &plaintext, |b, plaintext| b.iter(|| ope.encrypt(black_box(plaintext))));
+    }
+
+    let in_range: ValueRange = ValueRange::new(0_f64, 2_f64.powi(50) - 1_f64);
+    let out_range: ValueRange = ValueRange::new(0_f64, 2_f64.powi(100) - 1_f64);
+    let ope: OPE = OPE::new_wide("key", &in_range, &out_range);
+
+    group.bench_with_input(BenchmarkId::new("encrypt_u128", 100), &(1_u64 << 48), |b, plaintext| b.iter(|| ope.encrypt_u128(black_box(*plaintext))));
+    group.finish();
+}
+
+fn bench_tape_gen (c: &mut Criterion) {
+    // Coins of a single value, drawn at each step of an encryption
+    let generator: HmacSha256AesCtr = HmacSha256AesCtr::default();
+
+    c.bench_function("tape_gen", |b| b.iter(|| generator.coins(b"key", black_box(123_456_f64))));
+
+    // Same, with the key set up once as the OPE does
+    let context = generator.context(b"key");
+    let input: [u8; 16] = 123_456_i128.to_be_bytes();
+    c.bench_function("tape_gen_context", |b| b.iter(|| HmacSha256AesCtr::next_coins(&mut generator.tape_from_context(&context, black_box(&input)))));
+}
+
+fn bench_rhyper (c: &mut Criterion) {
+    let coins: [u8; 128] = HmacSha256AesCtr::default().coins(b"key", 0_f64);
+
+    // Small samples go through hyp, larger ones through HRUA*
+    c.bench_function("rhyper_hyp", |b| b.iter(|| {
+        let mut refill = || coins;
+        HGD::rhyper(black_box(&8_f64), &1_000_f64, &9_000_f64, &mut PRNG::new(coins, Some(&mut refill)))
+    }));
+    c.bench_function("rhyper_hrua", |b| b.iter(|| {
+        let mut refill = || coins;
+        HGD::rhyper(black_box(&500_000_000_f64), &1_000_000_f64, &999_000_000_f64, &mut PRNG::new(coins, Some(&mut refill)))
+    }));
+}
+
+fn bench_loggam (c: &mut Criterion) {
+    // Below 7, loggam shifts its argument before the asymptotic series
+    c.bench_function("loggam_small", |b| b.iter(|| HGD::loggam(black_box(3.5_f64))));
+    c.bench_function("loggam_large", |b| b.iter(|| HGD::loggam(black_box(1_000_000.5_f64))));
+}
+
+criterion_group!(benches, bench_encrypt, bench_encrypt_batch, bench_encrypt_sizes, bench_tape_gen, bench_rhyper, bench_loggam);
+criterion_main!(benches);
@@ -0,0 +1,21 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use rust_opse::{ValueRange, sample_hgd};
+
+fn bench_sample_hgd (c: &mut Criterion) {
+    // Large enough ranges for sample_hgd to go through HRUA*
+    let in_range: ValueRange = ValueRange::new(0_f64, 1_000_000_f64);
+    let out_range: ValueRange = ValueRange::new(0_f64, 1_000_000_000_f64);
+
+    let mut coins: [u8; 128] = [0; 128];
+    for (index, coin) in coins.iter_mut().enumerate() {
+        *coin = ((index * 7 + index / 3) % 2) as u8;
+    }
+
+    c.bench_function("sample_hgd", |b| b.iter(|| {
+        sample_hgd(&in_range, &out_range, black_box(&500_000_000_f64), &coins, Some(&mut || coins))
+    }));
+}
+
+criterion_group!(benches, bench_sample_hgd);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+#![no_main]
+
+use std::convert::TryInto;
+
+use libfuzzer_sys::fuzz_target;
+use rust_opse::{OPE, OpeCompat};
+
+fuzz_target!(|data: &[u8]| {
+
+    // The first byte picks the tape encoding, the next 8 bytes are the value
+    // fed to tape_gen (any f64, NaN and infinities included) and the rest is
+    // the encryption key.
+
+    if data.len() < 9 {
+        return;
+    }
+
+    let compat: OpeCompat = if data[0] % 2 == 0 { OpeCompat::Native } else { OpeCompat::Pyope };
+    let value: f64 = f64::from_le_bytes(data[1..9].try_into().unwrap());
+
+    let ope: OPE = OPE::builder().key(&data[9..]).in_range(0_f64, 100_f64).out_range(0_f64, 1000_f64).compat(compat).build().unwrap();
+    let coins: [u8; 128] = ope.tape_gen_for_value(value);
+
+    assert!(coins.iter().all(|coin| *coin <= 1), "Coins must be binary units. Found {:?}", coins);
+    assert_eq!(coins.to_vec(), ope.tape_gen_for_value(value).to_vec());
+});
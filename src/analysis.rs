@@ -0,0 +1,79 @@
+#[derive(Clone, Debug, PartialEq)]
+pub struct GapStats {
+    // Statistics of the gaps between consecutive ciphertexts of a sorted
+    // sample. Gaps of a whole encrypted domain follow the partition : their
+    // mean is out_range size / in_range size, with a spread depending on the
+    // hypergeometric samples. Gaps far more regular than that, or a zero gap,
+    // hint at a badly sized out_range or a broken implementation.
+    //
+    // This is a diagnostic only, it guarantees nothing about security.
+    pub count: usize,
+    pub mean: f64,
+    pub variance: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+pub fn analyze_gaps (ciphertexts: &[f64]) -> GapStats {
+
+    // Gaps between consecutive ciphertexts, which must already be sorted in
+    // ascending order. The variance is the population variance of the gaps.
+
+    if ciphertexts.len() < 2 {
+        panic!("OPE : at least two ciphertexts are needed to measure gaps. Found {}", ciphertexts.len());
+    }
+
+    let gaps = || ciphertexts.windows(2).map(|pair| pair[1] - pair[0]);
+
+    if let Some(gap) = gaps().find(|gap| *gap < 0_f64) {
+        panic!("OPE : ciphertexts should be sorted in ascending order. Found a gap of {}", gap);
+    }
+
+    let count: usize = ciphertexts.len() - 1;
+    let mean: f64 = gaps().sum::<f64>() / count as f64;
+    let variance: f64 = gaps().map(|gap| (gap - mean) * (gap - mean)).sum::<f64>() / count as f64;
+
+    GapStats {
+        count,
+        mean,
+        variance,
+        min: gaps().fold(f64::INFINITY, f64::min),
+        max: gaps().fold(0_f64, f64::max),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::GapStats;
+    use super::analyze_gaps;
+
+    use crate::ope::OPE;
+    use crate::range::ValueRange;
+
+    #[test]
+    fn test_analyze_gaps () {
+        let stats: GapStats = analyze_gaps(&[0_f64, 2_f64, 3_f64, 9_f64]);
+        assert_eq!(stats, GapStats { count: 3, mean: 3_f64, variance: 14_f64 / 3_f64, min: 1_f64, max: 6_f64 });
+    }
+
+    #[test]
+    fn test_analyze_gaps_encrypted_domain () {
+        let ope: OPE = OPE::new("key", &ValueRange::new(0_f64, 1000_f64), &ValueRange::new(0_f64, 1_000_000_f64));
+        let ciphertexts: Vec<f64> = ValueRange::new(0_f64, 1000_f64).iter().map(|plaintext| ope.encrypt(&plaintext)).collect();
+        let stats: GapStats = analyze_gaps(&ciphertexts);
+
+        // Strictly increasing, about 1000 apart on average, and not evenly spread
+        assert_eq!(stats.count, 1000);
+        assert!(stats.min > 0_f64, "{:?}", stats);
+        assert!((stats.mean - 1000_f64).abs() < 100_f64, "{:?}", stats);
+        assert!(stats.variance > 0_f64 && stats.max > 2_f64 * stats.mean, "{:?}", stats);
+    }
+
+    #[test]
+    #[should_panic(expected = "OPE : ciphertexts should be sorted in ascending order. Found a gap of -2")]
+    fn test_analyze_gaps_unsorted () {
+        analyze_gaps(&[0_f64, 5_f64, 3_f64]);
+    }
+}
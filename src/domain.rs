@@ -0,0 +1,108 @@
+use core::convert::TryFrom;
+use core::fmt;
+
+mod sealed {
+    // Only the integer types below can be used with OPE
+    pub trait Sealed {}
+}
+
+pub trait OpeInteger: sealed::Sealed + Copy + fmt::Debug + PartialOrd {
+    // Integer type which can be used as a plaintext or ciphertext bound.
+    //
+    // The partition is computed on i128 values, which every bound below
+    // BOUND_LIMIT in magnitude converts to without loss. to_f64 is only
+    // meant for error messages and rounds beyond 2^53.
+    fn to_f64 (self) -> f64;
+    fn to_i128 (self) -> Option<i128>;
+    fn from_i128 (value: i128) -> Self;
+}
+
+pub trait OpeDomain: OpeInteger {
+    // Plaintext type of an OPE, encrypted into a wider ciphertext type
+    type Cipher: OpeInteger;
+}
+
+// 2^126, sizes of ranges bounded by integers strictly below it in magnitude
+// fit in an i128, and so does the edge of the range below its start
+pub const BOUND_LIMIT: i128 = 1_i128 << 126;
+
+macro_rules! impl_ope_integer {
+    ($($integer:ty),*) => {
+        $(
+            impl sealed::Sealed for $integer {}
+
+            impl OpeInteger for $integer {
+                fn to_f64 (self) -> f64 {
+                    self as f64
+                }
+
+                fn to_i128 (self) -> Option<i128> {
+                    i128::try_from(self).ok()
+                }
+
+                fn from_i128 (value: i128) -> Self {
+                    value as $integer
+                }
+            }
+        )*
+    };
+}
+
+impl_ope_integer!(u32, u64, i64, u128, i128);
+
+impl OpeDomain for u32 {
+    type Cipher = u64;
+}
+
+impl OpeDomain for u64 {
+    type Cipher = u128;
+}
+
+impl OpeDomain for i64 {
+    type Cipher = i128;
+}
+
+pub fn bounded_i128<T: OpeInteger> (value: T) -> Option<i128> {
+    // Convert the given integer, unless it is BOUND_LIMIT or more in magnitude
+    value.to_i128().filter(|converted| -BOUND_LIMIT < *converted && *converted < BOUND_LIMIT)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::BOUND_LIMIT;
+    use super::OpeInteger;
+    use super::bounded_i128;
+
+    #[test]
+    fn test_bound_limit () {
+        assert_eq!(BOUND_LIMIT, 2_i128.pow(126));
+    }
+
+    #[test]
+    fn test_conversions () {
+        assert_eq!(u32::MAX.to_f64(), 4_294_967_295_f64);
+        assert_eq!(u32::MAX.to_i128(), Some(4_294_967_295_i128));
+        assert_eq!(u32::from_i128(4_294_967_295_i128), u32::MAX);
+        assert_eq!(u64::MAX.to_i128(), Some(18_446_744_073_709_551_615_i128));
+        assert_eq!(u64::from_i128(18_446_744_073_709_551_615_i128), u64::MAX);
+        assert_eq!(i64::MIN.to_i128(), Some(-9_223_372_036_854_775_808_i128));
+        assert_eq!(i64::from_i128(-42_i128), -42_i64);
+        assert_eq!(u128::MAX.to_i128(), None);
+        assert_eq!(u128::from_i128(1_000_000_i128), 1_000_000_u128);
+    }
+
+    #[test]
+    fn test_bounded_i128 () {
+        assert_eq!(bounded_i128(u64::MAX), Some(u64::MAX as i128));
+        assert_eq!(bounded_i128(i64::MIN), Some(i64::MIN as i128));
+        assert_eq!(bounded_i128(BOUND_LIMIT - 1), Some(BOUND_LIMIT - 1));
+        assert_eq!(bounded_i128(1 - BOUND_LIMIT), Some(1 - BOUND_LIMIT));
+
+        assert_eq!(bounded_i128(BOUND_LIMIT), None);
+        assert_eq!(bounded_i128(-BOUND_LIMIT), None);
+        assert_eq!(bounded_i128(i128::MIN), None);
+        assert_eq!(bounded_i128(u128::MAX), None);
+    }
+}
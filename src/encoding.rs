@@ -0,0 +1,197 @@
+use alloc::string::String;
+use core::error::Error;
+use core::fmt;
+
+use super::range::ValueRange;
+
+// Each character is a digit in base 129 : 0 pads strings shorter than
+// max_len, and an ASCII character c is written c + 1, so that a string
+// sorts before any longer string it is a prefix of
+const BASE: f64 = 129_f64;
+
+// Largest max_len for which every encoding is an exact f64, 129^7 < 2^53
+pub const MAX_STRING_LEN: usize = 7;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EncodingError {
+    // Reasons for which a string and an integer cannot be converted
+    MaxLenTooLarge(usize),
+    TooLong { len: usize, max_len: usize },
+    NonAscii(char),
+    InvalidValue(f64),
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncodingError::MaxLenTooLarge(max_len) => write!(f, "OPE : max_len ({}) should not be greater than {}.", max_len, MAX_STRING_LEN),
+            EncodingError::TooLong { len, max_len } => write!(f, "OPE : string length ({}) should not be greater than max_len ({}).", len, max_len),
+            EncodingError::NonAscii(character) => write!(f, "OPE : strings should only contain ASCII characters. Found {:?}", character),
+            EncodingError::InvalidValue(value) => write!(f, "OPE : {} is not the encoding of any string.", value),
+        }
+    }
+}
+
+impl Error for EncodingError {}
+
+pub fn string_range (max_len: usize) -> ValueRange {
+
+    // in_range holding the encodings of all the strings of at most max_len
+    // characters, that is 129^max_len values starting at 0. An OPE meant to
+    // encrypt such strings must be built with it as in_range, or a larger one.
+
+    if max_len > MAX_STRING_LEN {
+        panic!("{}", EncodingError::MaxLenTooLarge(max_len));
+    }
+
+    let size: f64 = (0..max_len).fold(1_f64, |size, _| size * BASE);
+    ValueRange::new(0_f64, size - 1_f64)
+}
+
+pub fn encode_string_to_int (s: &str, max_len: usize) -> f64 {
+    try_encode_string_to_int(s, max_len).unwrap_or_else(|error| panic!("{}", error))
+}
+
+pub fn try_encode_string_to_int (s: &str, max_len: usize) -> Result<f64, EncodingError> {
+
+    // Map an ASCII string of at most max_len characters into string_range(max_len),
+    // so that a < b lexicographically if and only if the encoding of a is lower
+    // than the encoding of b.
+
+    if max_len > MAX_STRING_LEN {
+        return Err(EncodingError::MaxLenTooLarge(max_len));
+    }
+
+    if let Some(character) = s.chars().find(|character| !character.is_ascii()) {
+        return Err(EncodingError::NonAscii(character));
+    }
+
+    if s.len() > max_len {
+        return Err(EncodingError::TooLong { len: s.len(), max_len });
+    }
+
+    let mut value: f64 = 0_f64;
+    for index in 0..max_len {
+        let digit: u8 = s.as_bytes().get(index).map_or(0_u8, |byte| byte + 1_u8);
+        value = value * BASE + digit as f64;
+    }
+
+    Ok(value)
+}
+
+pub fn decode_int_to_string (value: f64, max_len: usize) -> String {
+    try_decode_int_to_string(value, max_len).unwrap_or_else(|error| panic!("{}", error))
+}
+
+pub fn try_decode_int_to_string (value: f64, max_len: usize) -> Result<String, EncodingError> {
+
+    // Inverse of try_encode_string_to_int. Values which do not encode any string,
+    // such as a character following padding, are rejected.
+
+    if max_len > MAX_STRING_LEN {
+        return Err(EncodingError::MaxLenTooLarge(max_len));
+    }
+
+    if !string_range(max_len).contains(&value) || value % 1_f64 != 0_f64 {
+        return Err(EncodingError::InvalidValue(value));
+    }
+
+    let mut digits: [u8; MAX_STRING_LEN] = [0; MAX_STRING_LEN];
+    let mut remainder: f64 = value;
+    for digit in digits[..max_len].iter_mut().rev() {
+        *digit = (remainder % BASE) as u8;
+        remainder = (remainder - *digit as f64) / BASE;
+    }
+
+    let len: usize = digits[..max_len].iter().take_while(|digit| **digit != 0).count();
+    if digits[len..max_len].iter().any(|digit| *digit != 0) {
+        return Err(EncodingError::InvalidValue(value));
+    }
+
+    Ok(digits[..len].iter().map(|digit| (digit - 1) as char).collect())
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::EncodingError;
+    use super::MAX_STRING_LEN;
+    use super::decode_int_to_string;
+    use super::encode_string_to_int;
+    use super::string_range;
+    use super::try_decode_int_to_string;
+    use super::try_encode_string_to_int;
+
+    use crate::ope::OPE;
+    use crate::range::ValueRange;
+
+    #[test]
+    fn test_string_range () {
+        assert_eq!(string_range(0).size(), 1_f64);
+        assert_eq!(string_range(1).size(), 129_f64);
+        assert_eq!(string_range(MAX_STRING_LEN).size(), 129_f64.powi(7));
+        assert!(string_range(MAX_STRING_LEN).size() < 2_f64.powi(53));
+    }
+
+    #[test]
+    fn test_encode_string_to_int () {
+        assert_eq!(encode_string_to_int("", 3), 0_f64);
+        assert_eq!(encode_string_to_int("a", 3), 98_f64 * 129_f64 * 129_f64);
+        assert_eq!(encode_string_to_int("ab", 2), 98_f64 * 129_f64 + 99_f64);
+        assert_eq!(encode_string_to_int("\u{7f}\u{7f}", 2), string_range(2).end);
+
+        assert_eq!(try_encode_string_to_int("abcd", 3), Err(EncodingError::TooLong { len: 4, max_len: 3 }));
+        assert_eq!(try_encode_string_to_int("é", 3), Err(EncodingError::NonAscii('é')));
+        assert_eq!(try_encode_string_to_int("a", 8), Err(EncodingError::MaxLenTooLarge(8)));
+    }
+
+    #[test]
+    fn test_encoding_preserves_order () {
+        // Every string of up to 3 characters taken from a few ASCII characters,
+        // padding and control characters included
+        let alphabet: [char; 6] = ['\u{0}', '\u{1}', ' ', 'A', 'a', '\u{7f}'];
+        let mut strings: Vec<String> = vec![String::new()];
+        for len in 1..=3 {
+            let previous: Vec<String> = strings.iter().filter(|s| s.len() == len - 1).cloned().collect();
+            for prefix in previous.iter() {
+                strings.extend(alphabet.iter().map(|character| format!("{}{}", prefix, character)));
+            }
+        }
+
+        for a in strings.iter() {
+            for b in strings.iter() {
+                assert_eq!(a < b, encode_string_to_int(a, 3) < encode_string_to_int(b, 3), "{:?} and {:?}", a, b);
+            }
+            assert_eq!(&decode_int_to_string(encode_string_to_int(a, 3), 3), a);
+        }
+    }
+
+    #[test]
+    fn test_decode_int_to_string () {
+        assert_eq!(decode_int_to_string(0_f64, 3), "");
+        assert_eq!(decode_int_to_string(encode_string_to_int("ope", 7), 7), "ope");
+        assert_eq!(decode_int_to_string(encode_string_to_int("OPE : 1", 7), 7), "OPE : 1");
+
+        // A character after the padding, or a value out of string_range
+        assert_eq!(try_decode_int_to_string(1_f64, 2), Err(EncodingError::InvalidValue(1_f64)));
+        assert_eq!(try_decode_int_to_string(129_f64.powi(2), 2), Err(EncodingError::InvalidValue(129_f64.powi(2))));
+        assert_eq!(try_decode_int_to_string(0.5_f64, 2), Err(EncodingError::InvalidValue(0.5_f64)));
+    }
+
+    #[test]
+    fn test_encrypt_strings () {
+        let ope: OPE = OPE::new("key", &string_range(3), &ValueRange::new(0_f64, 2_f64.powi(40)));
+        let names: [&str; 5] = ["Al", "Alex", "Alexis", "Bob", "bob"];
+
+        let ciphertexts: Vec<f64> = names.iter().map(|name| ope.encrypt(&encode_string_to_int(&name[..name.len().min(3)], 3))).collect();
+        assert!(ciphertexts.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(decode_int_to_string(ope.decrypt(&ciphertexts[3]), 3), "Bob");
+    }
+
+    #[test]
+    fn test_encoding_error_display () {
+        assert_eq!(format!("{}", EncodingError::TooLong { len: 4, max_len: 3 }), "OPE : string length (4) should not be greater than max_len (3).");
+        assert_eq!(format!("{}", EncodingError::MaxLenTooLarge(8)), "OPE : max_len (8) should not be greater than 7.");
+    }
+}
@@ -0,0 +1,217 @@
+use core::error::Error;
+use core::fmt;
+
+use super::range::{RangeError, ValueRange};
+use super::tape::COIN_BUDGET;
+
+// Errors of the OPE API, returned by its try_* methods and panicked with by
+// the others. Each kind of operation has its own enum, so that matching on
+// it only lists what can actually go wrong.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OpeError {
+    // Reasons for which an OPE cannot be built from given ranges
+    InRangeTooLarge { in_size: f64, out_size: f64 },
+    OutRangeTooLarge(ValueRange),
+    RangeTooLarge { bits: u32 },
+    InvalidWideRange(ValueRange),
+    InsufficientExpansion { in_size: f64, out_size: f64 },
+    InvalidRange(RangeError),
+    InexactBound(f64),
+    InvalidQuantization(f64),
+    MissingKey,
+    MissingInRange,
+    MissingOutRange,
+}
+
+impl fmt::Display for OpeError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OpeError::InRangeTooLarge { in_size, out_size } => write!(f, "OPE : in_range size ({}) should not be greater than out_range size ({}).", in_size, out_size),
+            OpeError::OutRangeTooLarge(out_range) => write!(f, "OPE : out_range {:?} holds more than 2^53 values, which f64 ciphertexts cannot represent exactly. Use OPE::try_new_wide and encrypt_u128 for such a range.", out_range),
+            OpeError::RangeTooLarge { bits } => write!(f, "OPE : out_range needs {} bits of coins to sample a ciphertext, more than the {} coins of a tape.", bits, COIN_BUDGET),
+            OpeError::InvalidWideRange(out_range) => write!(f, "OPE : out_range should be within [0, 2^127) to encrypt into u128. Current out_range is {:?}.", out_range),
+            OpeError::InsufficientExpansion { in_size, out_size } => write!(f, "OPE : out_range size ({}) should be at least twice in_range size ({}) in strict mode.", out_size, in_size),
+            OpeError::InvalidRange(error) => write!(f, "OPE : invalid range. {}", error),
+            OpeError::InexactBound(bound) => write!(f, "OPE : bound ({}) is too large to be handled exactly, its magnitude should be below 2^126.", bound),
+            OpeError::InvalidQuantization(scale) => write!(f, "OPE : quantization ({}) should be a finite number greater than 0.", scale),
+            OpeError::MissingKey => write!(f, "OPE : an encryption key is required."),
+            OpeError::MissingInRange => write!(f, "OPE : an in_range is required."),
+            OpeError::MissingOutRange => write!(f, "OPE : an out_range is required."),
+        }
+    }
+}
+
+impl Error for OpeError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EncryptError {
+    // Reasons for which a plaintext cannot be encrypted
+    OutOfRange { plaintext: f64, in_range: ValueRange },
+    NonFinite(f64),
+    NotInteger(f64),
+    WideOutRange(ValueRange),
+    DomainTooLarge { in_size: f64, limit: f64 },
+    InvalidQuery { lo: f64, hi: f64 },
+    Internal(RangeError),
+    Panicked,
+}
+
+impl fmt::Display for EncryptError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncryptError::OutOfRange { plaintext, in_range } => write!(f, "OPE : plaintext ({}) should be in in_range {:?}.", plaintext, in_range),
+            EncryptError::NonFinite(plaintext) => write!(f, "OPE : plaintext should be a finite f64 number. Found {}", plaintext),
+            EncryptError::NotInteger(plaintext) => write!(f, "OPE : plaintext should be an integer. Found {}", plaintext),
+            EncryptError::WideOutRange(out_range) => write!(f, "OPE : out_range {:?} holds more than 2^53 values, plaintexts should be encrypted with encrypt_u128.", out_range),
+            EncryptError::DomainTooLarge { in_size, limit } => write!(f, "OPE : in_range holds {} plaintexts, at most {} can be encrypted at once.", in_size, limit),
+            EncryptError::InvalidQuery { lo, hi } => write!(f, "OPE : range query lower bound ({}) should not be greater than its upper bound ({}).", lo, hi),
+            EncryptError::Internal(error) => write!(f, "OPE : invalid partition during encryption. {}", error),
+            EncryptError::Panicked => write!(f, "OPE : encryption panicked, which is a bug of the OPE or of its tape generator."),
+        }
+    }
+}
+
+impl Error for EncryptError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DecryptError {
+    // Reasons for which a ciphertext cannot be decrypted
+    OutOfRange { ciphertext: f64, out_range: ValueRange },
+    NotInteger(f64),
+    NoMatch(f64),
+    WideOutRange(ValueRange),
+    InRangeTooLarge { in_size: f64, out_size: f64 },
+    InvalidLength { len: usize, expected: usize },
+    Internal(RangeError),
+}
+
+impl fmt::Display for DecryptError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecryptError::OutOfRange { ciphertext, out_range } => write!(f, "OPE : ciphertext ({}) should be in out_range {:?}.", ciphertext, out_range),
+            DecryptError::NotInteger(ciphertext) => write!(f, "OPE : ciphertext should be an integer. Found {}", ciphertext),
+            DecryptError::NoMatch(ciphertext) => write!(f, "OPE : invalid ciphertext ({}), it does not match any plaintext.", ciphertext),
+            DecryptError::WideOutRange(out_range) => write!(f, "OPE : out_range {:?} holds more than 2^53 values, f64 ciphertexts cannot be decrypted exactly.", out_range),
+            DecryptError::InRangeTooLarge { in_size, out_size } => write!(f, "OPE : in_range size ({}) should not be greater than out_range size ({}).", in_size, out_size),
+            DecryptError::InvalidLength { len, expected } => write!(f, "OPE : ciphertext should be written with {} bytes for this out_range. Found {}", expected, len),
+            DecryptError::Internal(error) => write!(f, "OPE : invalid partition during decryption. {}", error),
+        }
+    }
+}
+
+impl Error for DecryptError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigError {
+    // Reasons for which bytes cannot be read back into an OpeConfig
+    Truncated { len: usize, expected: usize },
+    InvalidLength(usize),
+    InvalidRange(RangeError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Truncated { len, expected } => write!(f, "OPE : configuration is truncated, {} bytes were found where {} are expected.", len, expected),
+            ConfigError::InvalidLength(len) => write!(f, "OPE : configuration should hold the 32 bytes of four range bounds. Found {}", len),
+            ConfigError::InvalidRange(error) => write!(f, "OPE : invalid range in configuration. {}", error),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+
+#[cfg(test)]
+mod tests {
+
+    use std::error::Error;
+
+    use super::ConfigError;
+    use super::DecryptError;
+    use super::EncryptError;
+    use super::OpeError;
+    use super::RangeError;
+    use super::ValueRange;
+
+    fn message (error: &dyn Error) -> String {
+        // Errors are only used through std::error::Error below
+        format!("{}", error)
+    }
+
+    #[test]
+    fn test_ope_error_display () {
+        let range: ValueRange = ValueRange::new(0_f64, 10_f64);
+
+        let expected: Vec<(OpeError, &str)> = vec![
+            (OpeError::InRangeTooLarge { in_size: 11_f64, out_size: 6_f64 }, "OPE : in_range size (11) should not be greater than out_range size (6)."),
+            (OpeError::OutRangeTooLarge(range.clone()), "OPE : out_range ValueRange { start: 0.0, end: 10.0 } holds more than 2^53 values, which f64 ciphertexts cannot represent exactly. Use OPE::try_new_wide and encrypt_u128 for such a range."),
+            (OpeError::RangeTooLarge { bits: 130 }, "OPE : out_range needs 130 bits of coins to sample a ciphertext, more than the 128 coins of a tape."),
+            (OpeError::InvalidWideRange(range.clone()), "OPE : out_range should be within [0, 2^127) to encrypt into u128. Current out_range is ValueRange { start: 0.0, end: 10.0 }."),
+            (OpeError::InsufficientExpansion { in_size: 11_f64, out_size: 20_f64 }, "OPE : out_range size (20) should be at least twice in_range size (11) in strict mode."),
+            (OpeError::InvalidRange(RangeError::NonFinite(f64::NAN)), "OPE : invalid range. ValueRange : bounds should be finite f64 numbers. Found NaN"),
+            (OpeError::InexactBound(1e40_f64), "OPE : bound (10000000000000000000000000000000000000000) is too large to be handled exactly, its magnitude should be below 2^126."),
+            (OpeError::InvalidQuantization(-1_f64), "OPE : quantization (-1) should be a finite number greater than 0."),
+            (OpeError::MissingKey, "OPE : an encryption key is required."),
+            (OpeError::MissingInRange, "OPE : an in_range is required."),
+            (OpeError::MissingOutRange, "OPE : an out_range is required."),
+        ];
+
+        for (error, text) in expected.iter() {
+            assert_eq!(message(error), *text);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_error_display () {
+        let range: ValueRange = ValueRange::new(0_f64, 10_f64);
+
+        let expected: Vec<(EncryptError, &str)> = vec![
+            (EncryptError::OutOfRange { plaintext: 11_f64, in_range: range.clone() }, "OPE : plaintext (11) should be in in_range ValueRange { start: 0.0, end: 10.0 }."),
+            (EncryptError::NonFinite(f64::INFINITY), "OPE : plaintext should be a finite f64 number. Found inf"),
+            (EncryptError::NotInteger(4.5_f64), "OPE : plaintext should be an integer. Found 4.5"),
+            (EncryptError::WideOutRange(range.clone()), "OPE : out_range ValueRange { start: 0.0, end: 10.0 } holds more than 2^53 values, plaintexts should be encrypted with encrypt_u128."),
+            (EncryptError::DomainTooLarge { in_size: 2e6_f64, limit: 1_048_576_f64 }, "OPE : in_range holds 2000000 plaintexts, at most 1048576 can be encrypted at once."),
+            (EncryptError::InvalidQuery { lo: 5_f64, hi: 3_f64 }, "OPE : range query lower bound (5) should not be greater than its upper bound (3)."),
+            (EncryptError::Internal(RangeError::InvalidSplit { mid: 10_f64, start: 0_f64, end: 10_f64 }), "OPE : invalid partition during encryption. ValueRange : split point (10) should be an integer from start value (0) to end value (10) excluded."),
+            (EncryptError::Panicked, "OPE : encryption panicked, which is a bug of the OPE or of its tape generator."),
+        ];
+
+        for (error, text) in expected.iter() {
+            assert_eq!(message(error), *text);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_error_display () {
+        let range: ValueRange = ValueRange::new(0_f64, 10_f64);
+
+        let expected: Vec<(DecryptError, &str)> = vec![
+            (DecryptError::OutOfRange { ciphertext: 11_f64, out_range: range.clone() }, "OPE : ciphertext (11) should be in out_range ValueRange { start: 0.0, end: 10.0 }."),
+            (DecryptError::NotInteger(4.5_f64), "OPE : ciphertext should be an integer. Found 4.5"),
+            (DecryptError::NoMatch(3_f64), "OPE : invalid ciphertext (3), it does not match any plaintext."),
+            (DecryptError::WideOutRange(range.clone()), "OPE : out_range ValueRange { start: 0.0, end: 10.0 } holds more than 2^53 values, f64 ciphertexts cannot be decrypted exactly."),
+            (DecryptError::InRangeTooLarge { in_size: 11_f64, out_size: 6_f64 }, "OPE : in_range size (11) should not be greater than out_range size (6)."),
+            (DecryptError::InvalidLength { len: 2, expected: 3 }, "OPE : ciphertext should be written with 3 bytes for this out_range. Found 2"),
+            (DecryptError::Internal(RangeError::StartGreaterThanEnd { start: 1_f64, end: 0_f64 }), "OPE : invalid partition during decryption. ValueRange : start value (1) should not be greater than end value (0)."),
+        ];
+
+        for (error, text) in expected.iter() {
+            assert_eq!(message(error), *text);
+        }
+    }
+
+    #[test]
+    fn test_config_error_display () {
+        let expected: Vec<(ConfigError, &str)> = vec![
+            (ConfigError::Truncated { len: 10, expected: 36 }, "OPE : configuration is truncated, 10 bytes were found where 36 are expected."),
+            (ConfigError::InvalidLength(40), "OPE : configuration should hold the 32 bytes of four range bounds. Found 40"),
+            (ConfigError::InvalidRange(RangeError::NonFinite(f64::INFINITY)), "OPE : invalid range in configuration. ValueRange : bounds should be finite f64 numbers. Found inf"),
+        ];
+
+        for (error, text) in expected.iter() {
+            assert_eq!(message(error), *text);
+        }
+    }
+}
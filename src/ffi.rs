@@ -0,0 +1,175 @@
+// C ABI over OPE, for bindings in other languages (Python's ctypes or cffi,
+// Node's ffi-napi...). The crate has to be built as a C library for them,
+// with cargo rustc --features ffi --crate-type cdylib for instance.
+//
+// An OPE is handed out as an opaque pointer, created by ope_new and released
+// by ope_free. Every function returns one of the OPE_* codes below, results
+// being written through out pointers, and panics never cross the boundary.
+
+use std::os::raw::c_int;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::slice;
+
+use super::ope::{EncryptError, OPE};
+use super::range::ValueRange;
+
+pub const OPE_OK: c_int = 0;
+pub const OPE_NULL_POINTER: c_int = 1;
+pub const OPE_INVALID_RANGE: c_int = 2;
+pub const OPE_OUT_OF_RANGE: c_int = 3;
+pub const OPE_PANIC: c_int = 4;
+
+fn guard (call: impl FnOnce() -> c_int) -> c_int {
+    // Turn a panic into an error code, unwinding into C being undefined
+    catch_unwind(AssertUnwindSafe(call)).unwrap_or(OPE_PANIC)
+}
+
+/// # Safety
+///
+/// `key` must point to `key_len` readable bytes, or may be null when `key_len`
+/// is 0. The key is read as raw bytes, it does not need to be NUL-terminated
+/// and may contain NUL bytes. `ope` must be null or point to writable memory
+/// for a pointer. On success, the caller owns the OPE written there and must
+/// release it exactly once with `ope_free`.
+#[no_mangle]
+pub unsafe extern "C" fn ope_new (key: *const u8, key_len: usize, in_start: f64, in_end: f64, out_start: f64, out_end: f64, ope: *mut *mut OPE) -> c_int {
+
+    // Build an OPE keyed with the key_len bytes at key, and write it to ope.
+    // key may only be null if key_len is 0. The OPE must be released with
+    // ope_free.
+
+    guard(|| {
+        if ope.is_null() || (key.is_null() && key_len != 0) {
+            return OPE_NULL_POINTER;
+        }
+
+        let key: &[u8] = if key_len == 0 { &[] } else { slice::from_raw_parts(key, key_len) };
+
+        let (in_range, out_range): (ValueRange, ValueRange) = match (ValueRange::try_new(in_start, in_end), ValueRange::try_new(out_start, out_end)) {
+            (Ok(in_range), Ok(out_range)) => (in_range, out_range),
+            _ => return OPE_INVALID_RANGE,
+        };
+
+        match OPE::try_new(key, &in_range, &out_range) {
+            Ok(built) => {
+                *ope = Box::into_raw(Box::new(built));
+                OPE_OK
+            },
+            Err(_) => OPE_INVALID_RANGE,
+        }
+    })
+}
+
+/// # Safety
+///
+/// `ope` must be null or a pointer returned by `ope_new` which has not been
+/// released by `ope_free` yet. `ciphertext` must be null or point to writable
+/// memory for an f64. The OPE is only read, so it may be shared between
+/// threads calling `ope_encrypt` concurrently.
+#[no_mangle]
+pub unsafe extern "C" fn ope_encrypt (ope: *const OPE, plaintext: f64, ciphertext: *mut f64) -> c_int {
+
+    // Encrypt plaintext with an OPE built by ope_new, and write the result
+    // to ciphertext.
+
+    guard(|| {
+        if ope.is_null() || ciphertext.is_null() {
+            return OPE_NULL_POINTER;
+        }
+
+        match (*ope).try_encrypt(&plaintext) {
+            Ok(encrypted) => {
+                *ciphertext = encrypted;
+                OPE_OK
+            },
+            Err(EncryptError::OutOfRange { .. }) | Err(EncryptError::NonFinite(_)) | Err(EncryptError::NotInteger(_)) => OPE_OUT_OF_RANGE,
+            Err(EncryptError::WideOutRange(_)) | Err(EncryptError::DomainTooLarge { .. }) | Err(EncryptError::InvalidQuery { .. }) => OPE_INVALID_RANGE,
+            Err(EncryptError::Internal(_)) | Err(EncryptError::Panicked) => OPE_PANIC,
+        }
+    })
+}
+
+/// # Safety
+///
+/// `ope` must be null or a pointer returned by `ope_new` which has not been
+/// released yet. It takes ownership of the OPE : the pointer is dangling
+/// afterwards, and releasing it a second time (double free) or using it in
+/// `ope_encrypt` is undefined behaviour.
+#[no_mangle]
+pub unsafe extern "C" fn ope_free (ope: *mut OPE) -> c_int {
+
+    // Release an OPE built by ope_new. The bytes of its key and of its keyed
+    // HMAC states are wiped on drop. Null is ignored.
+
+    guard(|| {
+        if !ope.is_null() {
+            drop(Box::from_raw(ope));
+        }
+
+        OPE_OK
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use core::ptr;
+
+    use super::OPE_INVALID_RANGE;
+    use super::OPE_NULL_POINTER;
+    use super::OPE_OK;
+    use super::OPE_OUT_OF_RANGE;
+    use super::ope_encrypt;
+    use super::ope_free;
+    use super::ope_new;
+
+    use crate::ope::OPE;
+    use crate::range::ValueRange;
+
+    #[test]
+    fn test_ffi () {
+        let key: &[u8] = b"key";
+        let mut ope: *mut OPE = ptr::null_mut();
+        let mut ciphertext: f64 = -1_f64;
+
+        unsafe {
+            assert_eq!(ope_new(key.as_ptr(), key.len(), 0_f64, 100_f64, 0_f64, 1000_f64, &mut ope), OPE_OK);
+            assert!(!ope.is_null());
+
+            assert_eq!(ope_encrypt(ope, 42_f64, &mut ciphertext), OPE_OK);
+            assert_eq!(ciphertext, OPE::new("key", &ValueRange::new(0_f64, 100_f64), &ValueRange::new(0_f64, 1000_f64)).encrypt(&42_f64));
+
+            assert_eq!(ope_encrypt(ope, 101_f64, &mut ciphertext), OPE_OUT_OF_RANGE);
+            assert_eq!(ope_encrypt(ope, f64::NAN, &mut ciphertext), OPE_OUT_OF_RANGE);
+            assert_eq!(ope_encrypt(ope, 4.5_f64, &mut ciphertext), OPE_OUT_OF_RANGE);
+            assert_eq!(ope_encrypt(ope, 42_f64, ptr::null_mut()), OPE_NULL_POINTER);
+
+            assert_eq!(ope_free(ope), OPE_OK);
+            assert_eq!(ope_free(ptr::null_mut()), OPE_OK);
+        }
+    }
+
+    #[test]
+    fn test_ffi_errors () {
+        let key: &[u8] = b"key";
+        let mut ope: *mut OPE = ptr::null_mut();
+        let mut ciphertext: f64 = -1_f64;
+
+        unsafe {
+            // Swapped ranges, reversed bounds and non integer bounds
+            assert_eq!(ope_new(key.as_ptr(), key.len(), 0_f64, 1000_f64, 0_f64, 100_f64, &mut ope), OPE_INVALID_RANGE);
+            assert_eq!(ope_new(key.as_ptr(), key.len(), 100_f64, 0_f64, 0_f64, 1000_f64, &mut ope), OPE_INVALID_RANGE);
+            assert_eq!(ope_new(key.as_ptr(), key.len(), 0_f64, 0.5_f64, 0_f64, 1000_f64, &mut ope), OPE_INVALID_RANGE);
+            assert!(ope.is_null());
+
+            assert_eq!(ope_new(ptr::null(), 3, 0_f64, 100_f64, 0_f64, 1000_f64, &mut ope), OPE_NULL_POINTER);
+            assert_eq!(ope_new(key.as_ptr(), key.len(), 0_f64, 100_f64, 0_f64, 1000_f64, ptr::null_mut()), OPE_NULL_POINTER);
+            assert_eq!(ope_encrypt(ptr::null(), 42_f64, &mut ciphertext), OPE_NULL_POINTER);
+
+            // An empty key is still a key
+            assert_eq!(ope_new(ptr::null(), 0, 0_f64, 100_f64, 0_f64, 1000_f64, &mut ope), OPE_OK);
+            assert_eq!(ope_free(ope), OPE_OK);
+        }
+    }
+}
@@ -1,29 +1,162 @@
-use std::cmp::Ordering;
+use core::cmp::Ordering;
+
+use core::f32::consts::PI as PI_32;
+
+use core::f64::consts::PI as PI_64;
+
+#[cfg(not(any(feature = "std", test)))]
+use super::math::FloatMath;
+// Whatever the platform and std, so that ciphertexts do not depend on them
+use super::math::{ceil, floor, ln, sqrt};
+
+#[cfg(feature = "rand")]
+use rand::Rng;
+
+#[cfg(feature = "rand")]
+use super::util::bits_of;
+use super::util::are_binary;
+
+// Corrections to the Rocktaeschel approximation used by HGD::loggam
+#[allow(clippy::excessive_precision)]
+const LOGGAM_COEFFICIENTS: [f64; 10] = [
+    8.333_333_333_333_333e-02, -2.777_777_777_777_778e-03,
+    7.936_507_936_507_937e-04, -5.952_380_952_380_952e-04,
+    8.417_508_417_508_418e-04, -1.917_526_917_526_918e-03,
+    6.410_256_410_256_410e-03, -2.955_065_359_477_124e-02,
+    1.796_443_723_688_307e-01, -1.392_432_216_905_900e+00
+];
+
+// Constants of HRUA*, the same f64 values as numpy's d1 and d2 bit for bit.
+// Any other rounding of them changes the samples, and so every ciphertext.
+pub const HRUA_D1: f64 = 1.715_527_769_921_413_5;
+pub const HRUA_D2: f64 = 0.898_916_162_058_898_8;
+
+// Samples above which HGD::rhyper uses HRUA* rather than hyp, as numpy does
+pub const HRUA_THRESHOLD: f64 = 10_f64;
+
+pub trait CoinSource {
+    // Source of the uniform draws of the samplers, from 0 to 1 included.
+    // PRNG reads them from coin tapes, other sources can replay recorded
+    // draws or take them from elsewhere.
+    fn next_draw (&mut self) -> f64;
+}
 
-use std::f32::consts::PI as PI_32;
+enum Refill<'a, const N: usize> {
+    // Where the PRNG takes its next tape of coins from
+    Empty,
+    Tape(&'a mut dyn FnMut() -> [u8; N]),
+    #[cfg(feature = "rand")]
+    Rng(&'a mut dyn Rng),
+}
 
-use std::f64::consts::PI as PI_64;
-use std::f64::EPSILON as EPSILON_64;
+pub struct PRNG<'a, const N: usize = 128> {
+    // Pseudo-random number generator reading 32 coins (bits) at a time
+    // from a tape of N coins, 128 by default as the tapes of an OPE.
+    //
+    // When the tape is exhausted, a fresh one is requested from the refill
+    // function. Without one, running out of coins is an error. The last
+    // N % 32 coins of a tape are never read.
+    coins: [u8; N],
+    cursor: usize,
+    refill: Refill<'a, N>,
+}
 
+impl<'a, const N: usize> PRNG<'a, N> {
+    pub fn new (coins: [u8; N], refill: Option<&'a mut dyn FnMut() -> [u8; N]>) -> PRNG<'a, N> {
+        if N < 32 {
+            panic!("PRNG : tapes should hold at least 32 coins. Found {}", N);
+        }
 
-struct PRNG {
-    coins: [u8; 32]
-}
+        // Coins are only checked in debug builds, a non binary coin would
+        // otherwise silently skew the draws
+        debug_assert!(are_binary(&coins), "Coins must be binary units. Found {:?}", coins);
+
+        let refill: Refill<N> = match refill {
+            Some(refill) => Refill::Tape(refill),
+            None => Refill::Empty,
+        };
+
+        PRNG { coins, cursor: 0, refill }
+    }
+    fn numerify_coins (&mut self) -> u32 {
+        if self.cursor + 32 > self.coins.len() {
+            self.coins = match &mut self.refill {
+                Refill::Empty => panic!("Not enough coins."),
+                Refill::Tape(refill) => refill(),
+                #[cfg(feature = "rand")]
+                Refill::Rng(rng) => rng_coins(*rng),
+            };
+            debug_assert!(are_binary(&self.coins), "Coins must be binary units. Found {:?}", self.coins);
+            self.cursor = 0;
+        }
 
-impl PRNG {
-    fn numerify_coins (&self) -> u32 {
         let mut out: u32 = 0;
-        for bit in self.coins.iter() {
+        for bit in self.coins[self.cursor..self.cursor + 32].iter() {
             out = (out << 1) | *bit as u32;
         }
+        self.cursor += 32;
         out
     }
-    fn draw (&self) -> f64 {
+    fn draw (&mut self) -> f64 {
         (self.numerify_coins() as f64) / (2_u64.pow(32) - 1) as f64
     }
+    pub fn draw_uint (&mut self, bound: u64) -> u64 {
+        // Uniform integer of [0, bound), without going through floats. Draws
+        // are masked down to the bits of bound - 1, and rejected until one is
+        // below bound, which happens with probability over 1/2 each time.
+        // Bounds above 2^32 take two words of 32 coins per draw.
+        //
+        // The samplers keep using draw, so that they match numpy and pyope.
+        // This is for code drawing its own integers from a coin tape.
+        if bound == 0 {
+            panic!("PRNG : bound should be greater than 0.");
+        }
+
+        let bits: u32 = 64 - (bound - 1).leading_zeros();
+        let mask: u64 = if bits == 64 { u64::MAX } else { (1_u64 << bits) - 1 };
+
+        loop {
+            let mut draw: u64 = self.numerify_coins() as u64;
+            if bits > 32 {
+                draw = (draw << 32) | self.numerify_coins() as u64;
+            }
+
+            if draw & mask < bound {
+                return draw & mask;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rand")]
+impl<'a> PRNG<'a> {
+    pub fn from_rng<R: Rng> (rng: &'a mut R) -> PRNG<'a> {
+        // Take every tape from the given RNG instead of the HMAC based tapes,
+        // so that the samplers can be studied on their own. Tapes are then
+        // always of 128 coins.
+        PRNG { coins: rng_coins(rng), cursor: 0, refill: Refill::Rng(rng) }
+    }
 }
 
-fn afc (index: &u32) -> f32 {
+impl<'a, const N: usize> CoinSource for PRNG<'a, N> {
+    fn next_draw (&mut self) -> f64 {
+        self.draw()
+    }
+}
+
+#[cfg(feature = "rand")]
+fn rng_coins<R: Rng + ?Sized, const N: usize> (rng: &mut R) -> [u8; N] {
+    // Bits of N / 8 random bytes, rounded up
+    let mut bytes: Vec<u8> = vec![0; N.div_ceil(8)];
+    rng.fill_bytes(&mut bytes);
+
+    let mut coins: [u8; N] = [0; N];
+    coins.copy_from_slice(&bits_of(&bytes)[..N]);
+
+    coins
+}
+
+pub fn ln_factorial (index: &u64) -> f32 {
     // This function calculates logarithm of i factorial: ln(i!)
     // using Stirling's approximation
     //
@@ -36,6 +169,10 @@ fn afc (index: &u32) -> f32 {
     // This value can be corrected with second or thrid order coefficients
     // when using Taylor's development to get more accuracy with lower values
     // of n.
+    //
+    // The result is an f32 : it is within 1e-4 of ln(n!) for n = 2 and 3, and
+    // within a few f32 epsilons relatively beyond. Above 2^24, n itself is
+    // rounded to an f32 first, which keeps about the same relative precision.
     match index.cmp(&1) {
         Ordering::Less => 0.0,
         Ordering::Equal => 0.0,
@@ -60,45 +197,61 @@ pub struct HGD {
 }
 
 impl HGD {
-    pub fn rhyper(kk: &f64, nn1: &f64, nn2: &f64, coins: &[u8; 32]) -> f64 {
-        let prng = PRNG { coins: *coins };
-
-        if kk > &10_f64 {
-            HGD::hypergeometric_hrua(&prng, nn1, nn2, kk)
+    pub fn rhyper<C: CoinSource + ?Sized>(kk: &f64, nn1: &f64, nn2: &f64, prng: &mut C) -> f64 {
+        HGD::rhyper_with_threshold(kk, nn1, nn2, prng, HRUA_THRESHOLD)
+    }
+    pub fn rhyper_with_threshold<C: CoinSource + ?Sized>(kk: &f64, nn1: &f64, nn2: &f64, prng: &mut C, threshold: f64) -> f64 {
+        // Same as rhyper, samples above threshold going through HRUA*.
+        //
+        // hyp draws a coin word per ball, so its cost grows with kk, while
+        // HRUA* draws a few words whatever kk. Both follow the hypergeometric
+        // distribution, but HRUA* works from differences of loggam values,
+        // whose rounding weighs the most for small samples : a lower threshold
+        // is faster for samples just above it, and a little less accurate.
+        //
+        // The OPE always uses HRUA_THRESHOLD. Samples, and so ciphertexts,
+        // depend on the threshold, and would no longer match pyope's.
+        if kk > &threshold {
+            HGD::hypergeometric_hrua(prng, nn1, nn2, kk)
         } else {
-            HGD::hypergeometric_hyp(&prng, nn1, nn2, kk)
+            HGD::hypergeometric_hyp(prng, nn1, nn2, kk)
         }
     }
-    fn hypergeometric_hyp(prng: &PRNG, good: &f64, bad: &f64, sample: &f64) -> f64 {
+    fn hypergeometric_hyp<C: CoinSource + ?Sized>(prng: &mut C, good: &f64, bad: &f64, sample: &f64) -> f64 {
         let d1: f64 = *bad + *good - *sample;
 
         let d2: f64 = (*good).min(*bad);
 
-        let mut y: f64 = d2.clone();
-        let mut k: f64 = sample.clone();
+        let mut y: f64 = d2;
+        let mut k: f64 = *sample;
 
-        while y > 0.0 {
-            let u: f64 = prng.draw();
+        // One draw per ball, until the d2 balls of the smaller colour are
+        // all out. A draw does not always take one of them, so d2 does not
+        // bound the iterations, the sample size does : k reaching zero stops
+        // the loop. Counting iterations, rather than comparing k to zero,
+        // also stops it for samples that are not whole numbers, or are not
+        // positive, for which k would never be zero.
+        let iterations: u64 = ceil(*sample).max(0_f64) as u64;
 
-            y -= (u + y/(d1 + k)).floor();
-            k -= 1_f64;
-
-            if k == 0_f64 {
+        for _ in 0..iterations {
+            if y <= 0_f64 {
                 break;
             }
+
+            let u: f64 = prng.next_draw();
+
+            y -= floor(u + y/(d1 + k));
+            k -= 1_f64;
         }
 
         let mut z: f64 = d2 - y;
         if good > bad {
-            z = *sample as f64 - z;
+            z = *sample - z;
         }
 
         z
     }
-    fn hypergeometric_hrua(prng: &PRNG, good: &f64, bad: &f64, sample: &f64) -> f64 {
-        const D1: f64 = 1.715_527_769_921_413_5;
-        const D2: f64 = 0.898_916_162_058_898_8;
-
+    fn hypergeometric_hrua<C: CoinSource + ?Sized>(prng: &mut C, good: &f64, bad: &f64, sample: &f64) -> f64 {
         let mingoodbad: f64 = (*good).min(*bad);
         let maxgoodbad: f64 = (*good).max(*bad);
 
@@ -109,27 +262,38 @@ impl HGD {
         let d4: f64 = mingoodbad / popsize;
         let d5: f64 = 1.0_f64 - d4;
         let d6: f64 = m * d4 + 0.5_f64;
-        let d7: f64 = ((popsize - m) * *sample * d4 * d5 /(popsize - 1_f64) + 0.5).sqrt();
-        let d8: f64 = D1 * d7 + D2;
-        let d9: f64 = (m + 1_f64) * (mingoodbad + 1_f64) /(popsize + 2_f64);
+        let d7: f64 = sqrt((popsize - m) * *sample * d4 * d5 /(popsize - 1_f64) + 0.5);
+        let d8: f64 = HRUA_D1 * d7 + HRUA_D2;
+        // d9 is the mode of the distribution, an integer
+        let d9: f64 = floor((m + 1_f64) * (mingoodbad + 1_f64) /(popsize + 2_f64));
         let d10: f64 = HGD::loggam(d9 + 1_f64) + HGD::loggam(mingoodbad - d9 + 1_f64) + HGD::loggam(m - d9 + 1_f64) + HGD::loggam(maxgoodbad - m + d9 + 1_f64);
 
-        // 16 because this is a 16 decimal digit precision in D1 and D2
-        let d11: f64 = (m.min(mingoodbad) + 1.0).min((d6 + 16_f64 * d7).round());
+        // 16 because this is a 16 decimal digit precision in HRUA_D1 and HRUA_D2
+        let d11: f64 = (m.min(mingoodbad) + 1.0).min(floor(d6 + 16_f64 * d7));
 
-        let mut z: f64 = 0.0;
+        let mut z: f64;
 
+        // Every loggam argument below is a whole number, so ln_factorial could stand
+        // for loggam(n + 1). It is not used : its f32 result shifts T enough
+        // to flip acceptances, and so pyope ciphertexts. An f64 Stirling series
+        // speeds up sample_hgd alone but not encryption as a whole, while still
+        // differing from loggam by up to 3e-10.
+
+        // Rejection loop of the HRUA* algorithm. Each iteration consumes two
+        // draws, and about 1.9 iterations are needed on average (measured while
+        // encrypting a 1000 values domain into a 1e6 values range). The number
+        // of iterations is not bounded, so the PRNG should be refillable.
         loop {
-            let x: f64 = prng.draw();
-            let y: f64 = prng.draw();
+            let x: f64 = prng.next_draw();
+            let y: f64 = prng.next_draw();
             let w: f64 = d6 + d8 * (y - 0.5_f64) / x;
 
             // fast rejection
-            if w < EPSILON_64 || w >= d11 {
+            if w < 0_f64 || w >= d11 {
                 continue;
             }
 
-            z = w.floor();
+            z = floor(w);
             let t: f64 = d10 - (HGD::loggam(z + 1.0) + HGD::loggam(mingoodbad - z + 1.0) + HGD::loggam(m - z + 1.0) + HGD::loggam(maxgoodbad - m + z + 1_f64));
 
             // fast-acceptance
@@ -143,7 +307,7 @@ impl HGD {
             }
 
             // acceptance
-            if 2.0 * x.ln() <= t {
+            if 2.0 * ln(x) <= t {
                 break;
             }
         }
@@ -160,7 +324,7 @@ impl HGD {
 
         z
     }
-    fn loggam (x: f64) -> f64 {
+    pub fn loggam (x: f64) -> f64 {
         // This method is aimed at implementing log-gamma function computation
         // to support some of the distributions.
         //
@@ -172,19 +336,21 @@ impl HGD {
         // loggam(x) ~ (x - 0.5) * ln(x) - x + 0.5 * ln(2 * PI) as x goes to + infinity
         //
         // This approximation can be improved using some below values as corrections
+        //
+        // Arguments below 7 are shifted up to x + n first, then brought back
+        // with loggam(x) = loggam(x + n) - ln(x) - ... - ln(x + n - 1), which
+        // holds for any x > 0. Arguments below 0.5 are thus as precise as
+        // others, without the reflection formula. Gamma has poles at 0 and
+        // at every negative integer, so only positive arguments are accepted.
+
+        if x.is_nan() || x <= 0_f64 {
+            panic!("HGD : loggam argument should be greater than 0. Found {}", x);
+        }
 
-        let a: Vec<f64> = vec![
-            8.333_333_333_333_333e-02, -2.777_777_777_777_778e-03,
-            7.936_507_936_507_937e-04, -5.952_380_952_380_952e-04,
-            8.417_508_417_508_418e-04, -1.917_526_917_526_918e-03,
-            6.410_256_410_256_410e-03, -2.955_065_359_477_124e-02,
-            1.796_443_723_688_307e-01, -1.392_432_216_905_900e+00
-        ];
-
-        let mut x0: f64 = x.clone();
+        let mut x0: f64 = x;
         let mut n: u64 = 0;
 
-        if (x - 1.0).abs() < EPSILON_64 || (x - 2.0).abs() < EPSILON_64 {
+        if (x - 1.0).abs() < f64::EPSILON || (x - 2.0).abs() < f64::EPSILON {
             return 0.0
         }
 
@@ -195,19 +361,21 @@ impl HGD {
 
         let x2: f64 = 1.0 / (x0 * x0);
         let xp: f64 = 2.0 * PI_64;
-        let mut gl0: f64 = a[9];
+        let mut gl0: f64 = LOGGAM_COEFFICIENTS[9];
 
         for k in (0..=8).rev() {
             gl0 *= x2;
-            gl0 += a[k];
+            gl0 += LOGGAM_COEFFICIENTS[k];
         }
 
-        let mut gl: f64 = gl0 / x0 + 0.5 * xp.ln() + (x0 - 0.5) * x0.ln() - x0;
+        let mut gl: f64 = gl0 / x0 + 0.5 * ln(xp) + (x0 - 0.5) * ln(x0) - x0;
 
+        // x + k rather than x0 decremented n times, whose rounding errors
+        // are left in x0 - n and swamp the smallest arguments. Both are the
+        // same for the integers and halves the samplers use.
         if x <= 7.0 {
-            for _k in 1..=n {
-                gl -= (x0 - 1.0).ln();
-                x0 -= 1.0;
+            for k in (0..n).rev() {
+                gl -= ln(x + k as f64);
             }
         }
 
@@ -218,87 +386,266 @@ impl HGD {
 #[cfg(test)]
 mod tests {
 
-    use super::afc;
+    use super::CoinSource;
+    use super::ln_factorial;
     use super::HGD;
+    use super::HRUA_D1;
+    use super::HRUA_D2;
+    use super::HRUA_THRESHOLD;
     use super::PRNG;
 
-    use std::f32::EPSILON;
     use std::f32::consts::LN_2;
 
-    use std::f64::EPSILON as EPSILON_64;
+    use crate::testing::{pseudo_random_tape, repeat_word};
 
     #[test]
-    fn test_afc () {
+    fn test_hrua_constants () {
+        // numpy's d1 = 1.7155277699214135 and d2 = 0.8989161620588988
+        assert_eq!(HRUA_D1.to_le_bytes(), [0x98, 0x13, 0x33, 0x3f, 0xcd, 0x72, 0xfb, 0x3f]);
+        assert_eq!(HRUA_D2.to_le_bytes(), [0x1a, 0x71, 0xbc, 0xd3, 0xeb, 0xc3, 0xec, 0x3f]);
+        assert_eq!(HRUA_D1.to_bits(), "1.7155277699214135".parse::<f64>().unwrap().to_bits());
+        assert_eq!(HRUA_D2.to_bits(), "0.8989161620588988".parse::<f64>().unwrap().to_bits());
+    }
+
+    #[test]
+    #[allow(clippy::excessive_precision)]
+    fn test_ln_factorial () {
         // To test the result values, a few values were computed
         // using other methods.
-        assert!(afc(&1).abs() < EPSILON);
+        assert!(ln_factorial(&1).abs() < f32::EPSILON);
 
         // For low values (2 and 3), precision is not good enough to under
         // EPSILON precision. just use 1e-4 as boundary
-        assert!((afc(&2) - LN_2).abs() < 1e-04_f32);
-        assert!((afc(&3) - 1.791_759).abs() < 1e-04_f32);
+        assert!((ln_factorial(&2) - LN_2).abs() < 1e-04_f32);
+        assert!((ln_factorial(&3) - 1.791_759).abs() < 1e-04_f32);
 
-        assert!((afc(&4) - 3.178_053).abs() < EPSILON);
-        assert!((afc(&10) - 15.104_412).abs() < EPSILON);
-        assert!((afc(&15) - 27.899_271).abs() < EPSILON);
-        assert!((afc(&100) - 363.739_375).abs() < EPSILON);
+        assert!((ln_factorial(&4) - 3.178_053).abs() < f32::EPSILON);
+        assert!((ln_factorial(&10) - 15.104_412).abs() < f32::EPSILON);
+        assert!((ln_factorial(&15) - 27.899_271).abs() < f32::EPSILON);
+        assert!((ln_factorial(&100) - 363.739_375).abs() < f32::EPSILON);
+    }
+
+    fn create_tape (word: &[u8; 32]) -> [u8; 128] {
+        // Build a tape whose first 32 coins are the given word
+        let mut coins: [u8; 128] = [0; 128];
+        coins[..32].copy_from_slice(word);
+        coins
     }
 
     #[test]
     fn test_prng_numerify_coins () {
         let coins: [u8; 32] = [0; 32];
-        let prng = PRNG { coins: coins};
+        let mut prng = PRNG::new(create_tape(&coins), None);
         assert_eq!(prng.numerify_coins(), 0);
 
         let mut coins: [u8; 32] = [0; 32];
         coins[31] = 1;
-        let prng = PRNG { coins: coins};
+        let mut prng = PRNG::new(create_tape(&coins), None);
         assert_eq!(prng.numerify_coins(), 1);
 
         let mut coins: [u8; 32] = [0; 32];
         coins[30] = 1;
         coins[31] = 1;
-        let prng = PRNG { coins: coins};
+        let mut prng = PRNG::new(create_tape(&coins), None);
         assert_eq!(prng.numerify_coins(), 3);
 
         let mut coins: [u8; 32] = [0; 32];
         coins[0] = 1;
-        let prng = PRNG { coins: coins};
+        let mut prng = PRNG::new(create_tape(&coins), None);
         assert_eq!(prng.numerify_coins(), 2_u32.pow(31));
 
         let coins: [u8; 32] = [1; 32];
-        let prng = PRNG { coins: coins};
+        let mut prng = PRNG::new(create_tape(&coins), None);
         assert_eq!(prng.numerify_coins(), (2_u64.pow(32) - 1) as u32);
     }
 
     #[test]
     fn test_prng_draw () {
         let coins: [u8; 32] = [0; 32];
-        let prng = PRNG { coins: coins};
+        let mut prng = PRNG::new(create_tape(&coins), None);
         assert_eq!(prng.draw(), 0.0_f64);
 
         let mut coins: [u8; 32] = [0; 32];
         coins[31] = 1;
-        let prng = PRNG { coins: coins};
-        assert!((prng.draw() - 2.328_306_437e-10_f64).abs() < EPSILON_64);
+        let mut prng = PRNG::new(create_tape(&coins), None);
+        assert!((prng.draw() - 2.328_306_437e-10_f64).abs() < f64::EPSILON);
 
         let mut coins: [u8; 32] = [0; 32];
         coins[30] = 1;
         coins[31] = 1;
-        let prng = PRNG { coins: coins};
-        assert!((prng.draw() - 6.984_919_311e-10_f64).abs() < EPSILON_64);
+        let mut prng = PRNG::new(create_tape(&coins), None);
+        assert!((prng.draw() - 6.984_919_311e-10_f64).abs() < f64::EPSILON);
 
         let mut coins: [u8; 32] = [0; 32];
         coins[0] = 1;
-        let prng = PRNG { coins: coins};
-        assert!((prng.draw() - 0.500_000_000_116_415_3_f64).abs() < EPSILON_64);
+        let mut prng = PRNG::new(create_tape(&coins), None);
+        assert!((prng.draw() - 0.500_000_000_116_415_3_f64).abs() < f64::EPSILON);
 
         let coins: [u8; 32] = [1; 32];
-        let prng = PRNG { coins: coins};
+        let mut prng = PRNG::new(create_tape(&coins), None);
         assert_eq!(prng.draw(), 1.0_f64);
     }
 
     #[test]
+    fn test_prng_draw_advances () {
+        // Each draw consumes the next 32 coins of the tape
+        let mut coins: [u8; 128] = [0; 128];
+        coins[31] = 1;
+        coins[62] = 1;
+        coins[64] = 1;
+
+        let mut prng = PRNG::new(coins, None);
+        assert_eq!(prng.numerify_coins(), 1);
+        assert_eq!(prng.numerify_coins(), 2);
+        assert_eq!(prng.numerify_coins(), 2_u32.pow(31));
+        assert_eq!(prng.numerify_coins(), 0);
+
+        let mut prng = PRNG::new(coins, None);
+        let draws: Vec<f64> = (0..3).map(|_| prng.draw()).collect();
+        assert!(draws[0] != draws[1] && draws[1] != draws[2] && draws[0] != draws[2]);
+    }
+
+    #[test]
+    fn test_prng_tape_sizes () {
+        // Words are read the same way whatever the size of the tape, larger
+        // tapes only being refilled less often
+        let mut coins: [u8; 256] = [0; 256];
+        for (index, coin) in coins.iter_mut().enumerate() {
+            *coin = ((index * 7 + index / 3) % 2) as u8;
+        }
+        let mut short: [u8; 128] = [0; 128];
+        short.copy_from_slice(&coins[..128]);
+
+        let mut prng: PRNG<256> = PRNG::new(coins, None);
+        let mut default_prng: PRNG = PRNG::new(short, None);
+        assert_eq!(prng.draw(), default_prng.draw());
+        for _ in 1..4 {
+            assert_eq!(prng.numerify_coins(), default_prng.numerify_coins());
+        }
+
+        // 8 words before running out of coins
+        for _ in 4..8 {
+            prng.numerify_coins();
+        }
+        let mut refill = || [1; 256];
+        let mut prng: PRNG<256> = PRNG::new(coins, Some(&mut refill));
+        for _ in 0..8 {
+            assert_ne!(prng.numerify_coins(), u32::MAX);
+        }
+        assert_eq!(prng.numerify_coins(), u32::MAX);
+
+        // A single word per tape, and the coins beyond the last word skipped
+        let mut refill = || [1; 40];
+        let mut prng: PRNG<40> = PRNG::new([0; 40], Some(&mut refill));
+        assert_eq!(prng.numerify_coins(), 0);
+        assert_eq!(prng.numerify_coins(), u32::MAX);
+        assert_eq!(prng.numerify_coins(), u32::MAX);
+
+        // Samples only depend on the draws
+        let mut prng: PRNG<256> = PRNG::new(coins, None);
+        let mut default_prng: PRNG = PRNG::new(short, None);
+        assert_eq!(HGD::rhyper(&3_f64, &5_f64, &5_f64, &mut prng), HGD::rhyper(&3_f64, &5_f64, &5_f64, &mut default_prng));
+    }
+
+    #[test]
+    #[should_panic(expected = "tapes should hold at least 32 coins")]
+    fn test_prng_short_tape () {
+        PRNG::new([0; 16], None);
+    }
+
+    #[test]
+    fn test_prng_draw_uint () {
+        // Draws are the low bits of each word, the second one being rejected
+        let mut coins: [u8; 128] = [0; 128];
+        coins[30] = 1;
+        coins[62] = 1;
+        coins[63] = 1;
+        let mut prng = PRNG::new(coins, None);
+        assert_eq!(prng.draw_uint(3), 2);
+        assert_eq!(prng.draw_uint(3), 0);
+
+        let mut prng = PRNG::new(coins, None);
+        assert_eq!(prng.draw_uint(1), 0);
+        assert_eq!(prng.draw_uint(2_u64.pow(40)), 3 * 2_u64.pow(32));
+    }
+
+    #[test]
+    fn test_prng_draw_uint_uniform () {
+        // Every value of a bound which is not a power of 2 is drawn about as
+        // often : with a modulo, 0 to 3 would be drawn twice as often as 4 to 5
+        let mut seed: u64 = 42;
+        let mut refill = || pseudo_random_tape(&mut seed);
+        let mut prng = PRNG::new([0; 128], Some(&mut refill));
+        prng.cursor = 128;
+
+        let mut counts: [u32; 6] = [0; 6];
+        for _ in 0..60_000 {
+            counts[prng.draw_uint(6) as usize] += 1;
+        }
+
+        for count in counts.iter() {
+            assert!((*count as i64 - 10_000).abs() < 400, "{:?}", counts);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "PRNG : bound should be greater than 0.")]
+    fn test_prng_draw_uint_zero () {
+        PRNG::new([0; 128], None).draw_uint(0);
+    }
+
+    #[test]
+    fn test_prng_refill () {
+        let mut refills: u32 = 0;
+        let mut refill = || {
+            refills += 1;
+            [1; 128]
+        };
+
+        {
+            let mut prng = PRNG::new([0; 128], Some(&mut refill));
+            for _ in 0..4 {
+                assert_eq!(prng.numerify_coins(), 0);
+            }
+            for _ in 0..8 {
+                assert_eq!(prng.numerify_coins(), u32::MAX);
+            }
+        }
+
+        assert_eq!(refills, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough coins.")]
+    fn test_prng_not_enough_coins () {
+        let mut prng = PRNG::new([0; 128], None);
+        for _ in 0..5 {
+            prng.draw();
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Coins must be binary units.")]
+    fn test_prng_non_binary_coins () {
+        let mut coins: [u8; 128] = [0; 128];
+        coins[100] = 2;
+        PRNG::new(coins, None);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Coins must be binary units.")]
+    fn test_prng_non_binary_refill () {
+        let mut refill = || [3; 128];
+        let mut prng = PRNG::new([0; 128], Some(&mut refill));
+        for _ in 0..5 {
+            prng.draw();
+        }
+    }
+
+    #[test]
+    #[allow(clippy::excessive_precision, clippy::approx_constant)]
     fn test_hgd_loggam () {
         // Pre-calculated values where calculated using online calculator
         // at keisan.casio.com/exec/system/1180573442
@@ -313,37 +660,327 @@ mod tests {
         assert!((HGD::loggam(100.0) - 359.134_205_369_575).abs() < 1e-09_f64);
 
         // These are precisely computed since their values are known
-        assert!(HGD::loggam(1.0).abs() <  EPSILON_64);
-        assert!(HGD::loggam(2.0).abs() < EPSILON_64);
+        assert!(HGD::loggam(1.0).abs() <  f64::EPSILON);
+        assert!(HGD::loggam(2.0).abs() < f64::EPSILON);
 
         // These values are large enough to be compared to std::f32::EPSILON
-        assert!((HGD::loggam(1000.0) - 5_905.220_423_209_181_211).abs() < EPSILON_64);
+        assert!((HGD::loggam(1000.0) - 5_905.220_423_209_181_211).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_hgd_loggam_small () {
+        // Reference values of ln Gamma from math.lgamma of CPython, loggam
+        // itself shifting small arguments up rather than reflecting them
+        assert!((HGD::loggam(0.5) - 0.572_364_942_924_700_1).abs() < 1e-12_f64);
+        assert!((HGD::loggam(0.25) - 1.288_022_524_698_077_5).abs() < 1e-12_f64);
+        assert!((HGD::loggam(0.1) - 2.252_712_651_734_206).abs() < 1e-12_f64);
+        assert!((HGD::loggam(0.75) - 0.203_280_951_431_295_4).abs() < 1e-12_f64);
+
+        // loggam(x) ~ -ln(x) - 0.5772... x near 0
+        let x: f64 = 1e-10_f64;
+        assert!((HGD::loggam(x) - (-x.ln() - 0.577_215_664_901_532_9 * x)).abs() < 1e-12_f64);
+    }
+
+    #[test]
+    #[should_panic(expected = "HGD : loggam argument should be greater than 0. Found 0")]
+    fn test_hgd_loggam_zero () {
+        HGD::loggam(0_f64);
+    }
+
+    #[test]
+    fn test_hgd_loggam_non_positive () {
+        for x in [-0.5_f64, -1_f64, -7.5_f64, f64::NEG_INFINITY, f64::NAN] {
+            assert!(std::panic::catch_unwind(|| HGD::loggam(x)).is_err(), "loggam({})", x);
+        }
+    }
+
+    #[test]
+    fn test_ln_factorial_loggam () {
+        // ln_factorial(n) approximates ln(n!) = loggam(n + 1), with the precision of an f32
+        for n in [2_u64, 3, 5, 7, 10, 50, 100, 1000, 10_000].iter() {
+            let expected: f64 = HGD::loggam(*n as f64 + 1_f64);
+            let tolerance: f64 = 1e-04_f64.max(expected * f32::EPSILON as f64);
+            assert!((ln_factorial(n) as f64 - expected).abs() < tolerance, "ln_factorial({}) = {}, loggam = {}", n, ln_factorial(n), expected);
+        }
+
+        // Beyond u32, and beyond 2^24 where n is rounded to an f32
+        for n in [100_000_u64, 16_777_217, 1_000_000_000, 1 << 32, 1 << 40, 1_000_000_000_000_000].iter() {
+            let expected: f64 = HGD::loggam(*n as f64 + 1_f64);
+            let tolerance: f64 = expected * 4_f64 * f32::EPSILON as f64;
+            assert!((ln_factorial(n) as f64 - expected).abs() < tolerance, "ln_factorial({}) = {}, loggam = {}", n, ln_factorial(n), expected);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_rhyper_mean () {
+        use rand::{SeedableRng, XorShiftRng};
+
+        // Compare the empirical mean of many samples to the theoretical mean
+        // kk * nn1 / (nn1 + nn2), within 5 standard errors, for both samplers
+        let mut rng: XorShiftRng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let draws: u32 = 20_000;
+
+        for (kk, nn1, nn2) in [(8_f64, 30_f64, 70_f64), (500_f64, 1000_f64, 3000_f64)].iter() {
+            let mut prng = PRNG::from_rng(&mut rng);
+            let mean: f64 = (0..draws).map(|_| HGD::rhyper(kk, nn1, nn2, &mut prng)).sum::<f64>() / draws as f64;
+
+            let popsize: f64 = nn1 + nn2;
+            let expected: f64 = kk * nn1 / popsize;
+            let variance: f64 = expected * (nn2 / popsize) * (popsize - kk) / (popsize - 1_f64);
+            let tolerance: f64 = 5_f64 * (variance / draws as f64).sqrt();
+
+            assert!((mean - expected).abs() < tolerance, "Mean {} too far from {} for {:?}", mean, expected, (kk, nn1, nn2));
+        }
     }
 
     #[test]
     fn test_rhyper () {
-        let mut coins = [0; 32];
+        let mut coins: [u8; 32] = [0; 32];
         coins[0] = 1;
         coins[1] = 1;
+        let coins: [u8; 128] = repeat_word(&coins);
 
-        let prng = PRNG {coins : coins };
+        let mut refill = || coins;
 
         for i in 1..=10 {
-            assert_eq!(HGD::rhyper(&(i as f64), &2_f64, &3_f64, &coins), HGD::hypergeometric_hyp(&prng, &2_f64, &3_f64, &(i as f64)));
+            let mut prng = PRNG::new(coins, Some(&mut refill));
+            let expected: f64 = HGD::hypergeometric_hyp(&mut prng, &2_f64, &3_f64, &(i as f64));
+            assert_eq!(HGD::rhyper(&(i as f64), &2_f64, &3_f64, &mut PRNG::new(coins, Some(&mut refill))), expected);
         }
 
-        assert_eq!(HGD::rhyper(&11_f64, &20_f64, &20_f64, &coins), HGD::hypergeometric_hrua(&prng, &20_f64, &20_f64, &11_f64));
+        let mut prng = PRNG::new(coins, Some(&mut refill));
+        let expected: f64 = HGD::hypergeometric_hrua(&mut prng, &20_f64, &20_f64, &11_f64);
+        assert_eq!(HGD::rhyper(&11_f64, &20_f64, &20_f64, &mut PRNG::new(coins, Some(&mut refill))), expected);
+    }
+
+    #[test]
+    fn test_rhyper_threshold () {
+        use crate::tape::{HmacSha256AesCtr, TapeGenerator};
+
+        assert_eq!(HRUA_THRESHOLD, 10_f64);
+
+        let generator: HmacSha256AesCtr = HmacSha256AesCtr::default();
+        let (nn1, nn2): (f64, f64) = (30_f64, 70_f64);
+        let draws: u32 = 5_000;
+
+        for kk in [10_f64, 11_f64] {
+            // Same coins whatever the sampler
+            let mut hyp_tape = generator.tape(b"key", &[kk as u8]);
+            let mut hrua_tape = generator.tape(b"key", &[kk as u8]);
+            let coins: [u8; 128] = HmacSha256AesCtr::next_coins(&mut hyp_tape);
+            HmacSha256AesCtr::next_coins(&mut hrua_tape);
+            let mut hyp_refill = || HmacSha256AesCtr::next_coins(&mut hyp_tape);
+            let mut hrua_refill = || HmacSha256AesCtr::next_coins(&mut hrua_tape);
+            let mut hyp = PRNG::new(coins, Some(&mut hyp_refill));
+            let mut hrua = PRNG::new(coins, Some(&mut hrua_refill));
+
+            let popsize: f64 = nn1 + nn2;
+            let expected: f64 = kk * nn1 / popsize;
+            let variance: f64 = expected * (nn2 / popsize) * (popsize - kk) / (popsize - 1_f64);
+            let tolerance: f64 = 5_f64 * (variance / draws as f64).sqrt();
+
+            // Both samplers stay in the support of the distribution, and
+            // agree on its mean on either side of the default threshold
+            let samples: Vec<(f64, f64)> = (0..draws).map(|_| {
+                (HGD::rhyper_with_threshold(&kk, &nn1, &nn2, &mut hyp, 11_f64), HGD::rhyper_with_threshold(&kk, &nn1, &nn2, &mut hrua, 9_f64))
+            }).collect();
+
+            for (hyp_sample, hrua_sample) in samples.iter() {
+                assert!((0_f64..=kk).contains(hyp_sample) && (0_f64..=kk).contains(hrua_sample));
+            }
+
+            let hyp_mean: f64 = samples.iter().map(|sample| sample.0).sum::<f64>() / draws as f64;
+            let hrua_mean: f64 = samples.iter().map(|sample| sample.1).sum::<f64>() / draws as f64;
+            assert!((hyp_mean - expected).abs() < tolerance, "hyp mean {} too far from {} for kk = {}", hyp_mean, expected, kk);
+            assert!((hrua_mean - expected).abs() < tolerance, "HRUA* mean {} too far from {} for kk = {}", hrua_mean, expected, kk);
+        }
+
+        // The default threshold sends 10 to hyp and 11 to HRUA*
+        for (kk, threshold) in [(10_f64, 9_f64), (11_f64, 11_f64)] {
+            let samples: Vec<f64> = (0..5).map(|variant| {
+                let mut tape = generator.tape(b"key", b"threshold");
+                let coins: [u8; 128] = HmacSha256AesCtr::next_coins(&mut tape);
+                let mut refill = || HmacSha256AesCtr::next_coins(&mut tape);
+                let mut prng = PRNG::new(coins, Some(&mut refill));
+
+                match variant {
+                    0 => HGD::rhyper(&kk, &nn1, &nn2, &mut prng),
+                    1 => HGD::rhyper_with_threshold(&kk, &nn1, &nn2, &mut prng, HRUA_THRESHOLD),
+                    2 => HGD::rhyper_with_threshold(&kk, &nn1, &nn2, &mut prng, threshold),
+                    3 => HGD::hypergeometric_hyp(&mut prng, &nn1, &nn2, &kk),
+                    _ => HGD::hypergeometric_hrua(&mut prng, &nn1, &nn2, &kk),
+                }
+            }).collect();
+
+            let (default, other): (f64, f64) = if kk > HRUA_THRESHOLD { (samples[4], samples[3]) } else { (samples[3], samples[4]) };
+            assert_eq!(samples[0], default);
+            assert_eq!(samples[1], default);
+            assert_eq!(samples[2], other);
+        }
+    }
+
+    struct ScriptedDraws {
+        // Replays the given draws in order, recording how many were taken
+        draws: Vec<f64>,
+        taken: usize,
+    }
+
+    impl CoinSource for ScriptedDraws {
+        fn next_draw (&mut self) -> f64 {
+            let draw: f64 = self.draws[self.taken];
+            self.taken += 1;
+            draw
+        }
+    }
+
+    struct RecordedDraws<'a, 'b> {
+        prng: &'a mut PRNG<'b>,
+        draws: Vec<f64>,
+    }
+
+    impl<'a, 'b> CoinSource for RecordedDraws<'a, 'b> {
+        fn next_draw (&mut self) -> f64 {
+            let draw: f64 = self.prng.next_draw();
+            self.draws.push(draw);
+            draw
+        }
+    }
+
+    #[test]
+    fn test_rhyper_coin_source () {
+        // hyp draws once per ball, a single ball of [2 good, 3 bad] being
+        // good for draws of 0.6 and above
+        for (draw, expected) in [(0.7_f64, 1_f64), (0.5_f64, 0_f64), (1_f64, 1_f64), (0_f64, 0_f64)] {
+            let mut source: ScriptedDraws = ScriptedDraws { draws: vec![draw], taken: 0 };
+            assert_eq!(HGD::rhyper(&1_f64, &2_f64, &3_f64, &mut source), expected);
+            assert_eq!(source.taken, 1);
+        }
+
+        // Draws of a PRNG, replayed, give the same samples through either
+        // sampler, also as a trait object
+        let coins: [u8; 128] = repeat_word(&[1, 0, 1, 1, 0, 0, 1, 0, 1, 1, 1, 0, 0, 0, 1, 0, 1, 0, 0, 1, 1, 1, 0, 1, 0, 0, 1, 1, 0, 1, 0, 1]);
+        for (kk, nn1, nn2) in [(5_f64, 20_f64, 30_f64), (500_f64, 1000_f64, 3000_f64)] {
+            let mut refill = || coins;
+            let mut prng = PRNG::new(coins, Some(&mut refill));
+            let mut recorded: RecordedDraws = RecordedDraws { prng: &mut prng, draws: Vec::new() };
+            let expected: f64 = HGD::rhyper(&kk, &nn1, &nn2, &mut recorded);
+
+            let taken: usize = recorded.draws.len();
+            let mut source: ScriptedDraws = ScriptedDraws { draws: recorded.draws, taken: 0 };
+            let dyn_source: &mut dyn CoinSource = &mut source;
+            assert_eq!(HGD::rhyper(&kk, &nn1, &nn2, dyn_source), expected);
+            assert_eq!(source.taken, taken);
+        }
+    }
+
+    fn tapes_from_words (words: &[u32]) -> Vec<[u8; 128]> {
+        // Tapes holding the given draws, four 32 coins words per tape
+        words.chunks(4).map(|chunk| {
+            let mut coins: [u8; 128] = [0; 128];
+            for (index, word) in chunk.iter().enumerate() {
+                for bit in 0..32 {
+                    coins[32 * index + bit] = ((word >> (31 - bit)) & 1) as u8;
+                }
+            }
+            coins
+        }).collect()
+    }
+
+    #[test]
+    fn test_rhyper_numpy_vectors () {
+        // (good, bad, sample, draws, expected) vectors computed with a transcription
+        // of numpy's legacy rk_hypergeometric (randomkit's hyp, HRUA* and loggam),
+        // rk_double being replaced by the same draws as PRNG : 32 bits words
+        // divided by 2^32 - 1. Samples of 10 or less go through hyp, larger
+        // ones through HRUA*.
+        #[allow(clippy::type_complexity)]
+        let vectors: [(f64, f64, f64, &[u32], f64); 15] = [
+            (100_f64, 200_f64, 5_f64, &[0x1e35049e, 0x74988232, 0x6b94b5b5, 0x79c8c292, 0xc8f429a4, 0x4242aadb, 0x9588532d, 0xeb7a7995], 1_f64),
+            (200_f64, 100_f64, 10_f64, &[0x43514e2e, 0x4e5ac63a, 0x347a2e47, 0x167dd41a, 0x440a9a11, 0x23263c49, 0x398bdedf, 0xa30354ef, 0xd942b204, 0x98f01733, 0x57810ce8, 0x67c8765c], 9_f64),
+            (3_f64, 5_f64, 7_f64, &[0x10c01d5d, 0x5439f75f, 0xb32e9ea1, 0xfd39265b, 0xef605abf, 0x8ca4c608, 0xd6a19e4f, 0x616273cd], 3_f64),
+            (1000_f64, 1_f64, 10_f64, &[0x74645968, 0xd04883eb, 0x8e82c020, 0xa736357e, 0xf9d97768, 0x14f69a60, 0x3556173a, 0xa1a58ce8, 0xac125000, 0x76067ae6, 0x5d04c969, 0xfe5dd6f6], 10_f64),
+            (50_f64, 50_f64, 1_f64, &[0xba45cd54, 0xf62e7330, 0xc2cb1187, 0xe644c315], 1_f64),
+            (100_f64, 200_f64, 11_f64, &[0x4f2d1cae, 0x642b5dfc, 0x9e1e266c, 0xae1591f0], 2_f64),
+            (200_f64, 100_f64, 150_f64, &[0xe37af0d3, 0x652345e9, 0xe8ab4b4b, 0x38b04807], 101_f64),
+            (1000_f64, 10_f64, 500_f64, &[0x45b3d24d, 0xb997a621, 0x046e36cb, 0x1d2f3e74], 492_f64),
+            (5_f64, 100000_f64, 1000_f64, &[0xcbbd9bbc, 0x76eb3a7f, 0x4153402f, 0xe223388e], 0_f64),
+            (32768_f64, 2147450880_f64, 1073741824_f64, &[0x99199917, 0x5f1c8e98, 0x1c02973e, 0x87d1fbf5], 16350_f64),
+            (2001_f64, 198000_f64, 100001_f64, &[0x9562aa92, 0xd308a485, 0x270450ac, 0x366556d0], 979_f64),
+            (10_f64, 10_f64, 19_f64, &[0x2b87ce5d, 0x2db879ee, 0xaf2b8395, 0xe426ef39, 0x44319a55, 0x631996d9, 0xfd250f5d, 0xa11fae9e], 9_f64),
+            (1_f64, 999999_f64, 500000_f64, &[0x5782b320, 0x91cc3b27, 0x0f5729c5, 0xdc7db0b5], 1_f64),
+            (500_f64, 500_f64, 600_f64, &[0x9cd3278c, 0x12eabc13, 0x0feec51d, 0x5ff09ec9, 0x1594e329, 0x36aab32a, 0x8c15f170, 0xf795ded4, 0x14886912, 0x3e3905bb, 0x5ad846ea, 0x5a38021a], 307_f64),
+            (40_f64, 60_f64, 30_f64, &[0xc2400ae2, 0xdf5d9484, 0x7b08d221, 0x16df811d], 14_f64),
+        ];
+
+        for (good, bad, sample, words, expected) in vectors.iter() {
+            let tapes: Vec<[u8; 128]> = tapes_from_words(words);
+            let mut refills = tapes[1..].iter();
+            let mut refill = || *refills.next().expect("Not enough draws in the vector.");
+
+            let mut prng = PRNG::new(tapes[0], Some(&mut refill));
+            assert_eq!(HGD::rhyper(sample, good, bad, &mut prng), *expected, "rhyper({}, {}, {})", sample, good, bad);
+        }
+    }
+
+    #[test]
+    fn test_hypergeometric_hrua_many_rejections () {
+        // Zero coins draw 0, which is always rejected. Five such tapes make
+        // ten rejected candidates before the first one that can be accepted.
+        let mut coins: [u8; 32] = [0; 32];
+        coins[0] = 1;
+        coins[1] = 1;
+        let accepted: [u8; 128] = repeat_word(&coins);
+
+        let mut refills: u32 = 0;
+        let mut refill = || {
+            refills += 1;
+            if refills < 5 { [0; 128] } else { accepted }
+        };
+
+        let mut prng = PRNG::new([0; 128], Some(&mut refill));
+        assert_eq!(HGD::hypergeometric_hrua(&mut prng, &20_f64, &20_f64, &25_f64), 11.0);
     }
 
     #[test]
     fn test_hgd_hypergeometric_hyp () {
-        let coins: [u8; 32] = [1; 32];
-        let prng = PRNG { coins: coins};
-        assert_eq!(HGD::hypergeometric_hyp(&prng, &3_f64, &2_f64, &4_f64), 2.0);
+        let coins: [u8; 128] = [1; 128];
+        let mut refill = || coins;
 
-        let coins: [u8; 32] = [1; 32];
-        let prng = PRNG { coins: coins};
-        assert_eq!(HGD::hypergeometric_hyp(&prng, &19_f64, &4_f64, &56_f64), 52.0);
+        let mut prng = PRNG::new(coins, Some(&mut refill));
+        assert_eq!(HGD::hypergeometric_hyp(&mut prng, &3_f64, &2_f64, &4_f64), 2.0);
+
+        let mut prng = PRNG::new(coins, Some(&mut refill));
+        assert_eq!(HGD::hypergeometric_hyp(&mut prng, &19_f64, &4_f64, &56_f64), 52.0);
+    }
+
+    #[test]
+    fn test_hgd_hypergeometric_hyp_terminates () {
+        // Both balls of [1 good, 1 bad] are drawn, one of them being good,
+        // after one draw of 0.5 and above, or two draws
+        for draw in [0_f64, 0.3_f64, 0.5_f64, 0.9_f64] {
+            let mut source: ScriptedDraws = ScriptedDraws { draws: vec![draw; 2], taken: 0 };
+            assert_eq!(HGD::hypergeometric_hyp(&mut source, &1_f64, &1_f64, &2_f64), 1_f64);
+            assert_eq!(source.taken, if draw < 0.5_f64 { 2 } else { 1 });
+        }
+
+        // Never more draws than balls, the source panicking otherwise
+        for (good, bad, sample) in [(1_f64, 1_f64, 1_f64), (1_f64, 0_f64, 1_f64), (0_f64, 1_f64, 1_f64), (1_f64, 2_f64, 3_f64), (2_f64, 1_f64, 3_f64), (5_f64, 5_f64, 10_f64)] {
+            for draw in [0_f64, 0.5_f64, 1_f64] {
+                let mut source: ScriptedDraws = ScriptedDraws { draws: vec![draw; sample as usize], taken: 0 };
+                let z: f64 = HGD::hypergeometric_hyp(&mut source, &good, &bad, &sample);
+                assert!(source.taken <= sample as usize);
+                assert!(z <= sample && z <= good);
+            }
+        }
+
+        // Samples for which k never reaches zero
+        let mut source: ScriptedDraws = ScriptedDraws { draws: vec![], taken: 0 };
+        assert_eq!(HGD::hypergeometric_hyp(&mut source, &2_f64, &3_f64, &0_f64), 0_f64);
+        assert_eq!(HGD::hypergeometric_hyp(&mut source, &2_f64, &3_f64, &-1_f64), 0_f64);
+        let mut source: ScriptedDraws = ScriptedDraws { draws: vec![0_f64; 3], taken: 0 };
+        HGD::hypergeometric_hyp(&mut source, &2_f64, &3_f64, &2.5_f64);
+        assert_eq!(source.taken, 3);
     }
 
     #[test]
@@ -351,14 +988,18 @@ mod tests {
         let mut coins: [u8; 32] = [0; 32];
         coins[0] = 1;
         coins[1] = 1;
-        let prng = PRNG { coins: coins};
-        assert_eq!(HGD::hypergeometric_hrua(&prng, &20_f64, &20_f64, &25_f64), 11.0);
+        let coins: [u8; 128] = repeat_word(&coins);
+        let mut refill = || coins;
+        let mut prng = PRNG::new(coins, Some(&mut refill));
+        assert_eq!(HGD::hypergeometric_hrua(&mut prng, &20_f64, &20_f64, &25_f64), 11.0);
 
         let mut coins: [u8; 32] = [0; 32];
         coins[1] = 1;
         coins[2] = 1;
         coins[3] = 1;
-        let prng = PRNG { coins: coins};
-        assert_eq!(HGD::hypergeometric_hrua(&prng, &50_f64, &111_f64, &67_f64), 20.0);
+        let coins: [u8; 128] = repeat_word(&coins);
+        let mut refill = || coins;
+        let mut prng = PRNG::new(coins, Some(&mut refill));
+        assert_eq!(HGD::hypergeometric_hrua(&mut prng, &50_f64, &111_f64, &67_f64), 20.0);
     }
 }
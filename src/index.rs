@@ -0,0 +1,133 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+use super::error::EncryptError;
+use super::ope::OPE;
+use super::tape::{HmacSha256AesCtr, TapeGenerator};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct OrderedF64(f64);
+
+// Ciphertexts are finite integers, for which total_cmp is the usual order
+impl Eq for OrderedF64 {}
+
+impl PartialOrd for OrderedF64 {
+    fn partial_cmp (&self, other: &OrderedF64) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrderedF64 {
+    fn cmp (&self, other: &OrderedF64) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+pub struct OpeIndex<G: TapeGenerator = HmacSha256AesCtr> {
+    // Row ids sorted by the ciphertexts of their plaintexts, so that a range
+    // of plaintexts is looked up as a range of ciphertexts. Plaintexts are
+    // not kept, only the OPE to encrypt them and the query bounds.
+    //
+    // Equal plaintexts share a ciphertext, whose rows are kept in insertion
+    // order.
+    ope: OPE<f64, G>,
+    rows: BTreeMap<OrderedF64, Vec<u64>>,
+    len: usize,
+}
+
+impl<G: TapeGenerator> OpeIndex<G> {
+    pub fn new (ope: OPE<f64, G>) -> OpeIndex<G> {
+        OpeIndex { ope, rows: BTreeMap::new(), len: 0 }
+    }
+
+    pub fn insert (&mut self, plaintext: &f64, row_id: u64) {
+        self.try_insert(plaintext, row_id).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_insert (&mut self, plaintext: &f64, row_id: u64) -> Result<(), EncryptError> {
+        let ciphertext: f64 = self.ope.try_encrypt(plaintext)?;
+
+        self.rows.entry(OrderedF64(ciphertext)).or_default().push(row_id);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    pub fn range_query (&self, lo: &f64, hi: &f64) -> Vec<u64> {
+        self.try_range_query(lo, hi).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_range_query (&self, lo: &f64, hi: &f64) -> Result<Vec<u64>, EncryptError> {
+
+        // Rows of the plaintexts from lo to hi, both included, in plaintext
+        // order. Only the bounds are encrypted, the map being scanned between
+        // their ciphertexts.
+
+        let (lo, hi): (f64, f64) = self.ope.try_encrypt_range_query(lo, hi)?;
+
+        Ok(self.rows.range(OrderedF64(lo)..=OrderedF64(hi)).flat_map(|(_, rows)| rows.iter().copied()).collect())
+    }
+
+    pub fn len (&self) -> usize {
+        // Number of rows inserted, equal plaintexts included
+        self.len
+    }
+
+    pub fn is_empty (&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn ope (&self) -> &OPE<f64, G> {
+        &self.ope
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::OpeIndex;
+
+    use crate::error::EncryptError;
+    use crate::ope::OPE;
+    use crate::range::ValueRange;
+
+    fn create_index () -> OpeIndex {
+        OpeIndex::new(OPE::new("key", &ValueRange::new(-100_f64, 100_f64), &ValueRange::new(0_f64, 100_000_f64)))
+    }
+
+    #[test]
+    fn test_range_query () {
+        let mut index: OpeIndex = create_index();
+        assert!(index.is_empty());
+
+        // Row ids 0 to 7 for these plaintexts, 5 appearing twice
+        for (row_id, plaintext) in [42_f64, -100_f64, 5_f64, 100_f64, -3_f64, 5_f64, 0_f64, 17_f64].iter().enumerate() {
+            index.insert(plaintext, row_id as u64);
+        }
+        assert_eq!(index.len(), 8);
+
+        assert_eq!(index.range_query(&0_f64, &42_f64), vec![6, 2, 5, 7, 0]);
+        assert_eq!(index.range_query(&-100_f64, &100_f64), vec![1, 4, 6, 2, 5, 7, 0, 3]);
+        assert_eq!(index.range_query(&5_f64, &5_f64), vec![2, 5]);
+        assert_eq!(index.range_query(&6_f64, &16_f64), Vec::<u64>::new());
+        assert_eq!(index.range_query(&-2_f64, &0_f64), vec![6]);
+
+        assert_eq!(index.try_range_query(&42_f64, &0_f64), Err(EncryptError::InvalidQuery { lo: 42_f64, hi: 0_f64 }));
+        assert!(matches!(index.try_range_query(&0_f64, &101_f64), Err(EncryptError::OutOfRange { .. })));
+    }
+
+    #[test]
+    fn test_try_insert () {
+        let mut index: OpeIndex = create_index();
+
+        assert!(matches!(index.try_insert(&101_f64, 1), Err(EncryptError::OutOfRange { .. })));
+        assert_eq!(index.try_insert(&2.5_f64, 1), Err(EncryptError::NotInteger(2.5_f64)));
+        assert!(index.is_empty());
+
+        assert_eq!(index.try_insert(&2_f64, 1), Ok(()));
+        assert_eq!(index.range_query(&2_f64, &2_f64), vec![1]);
+        assert_eq!(index.ope().config().in_range, ValueRange::new(-100_f64, 100_f64));
+    }
+}
@@ -0,0 +1,167 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{Ordering, compiler_fence};
+
+use hmac::{Hmac, Mac};
+#[cfg(feature = "rand")]
+use rand::{OsRng, Rng};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Clone)]
+pub struct EncryptionKey(Vec<u8>);
+
+impl EncryptionKey {
+    pub fn new (encryption_key: impl AsRef<[u8]>) -> EncryptionKey {
+        // Keys are raw bytes, so that keys coming from a KDF or an HSM can
+        // be used as they are
+        EncryptionKey(encryption_key.as_ref().to_vec())
+    }
+
+    pub fn derive (master_key: &[u8], context: &[u8]) -> EncryptionKey {
+
+        // HKDF-SHA256 (RFC 5869) without salt, the context being the info
+        // parameter. A single block of output gives a 32 bytes key.
+
+        let mut extract = HmacSha256::new_from_slice(&[0_u8; 32]).expect("HMAC accepts keys of any size");
+        extract.update(master_key);
+        let mut prk: [u8; 32] = hmac_sha256_wiped(extract);
+
+        let mut expand = HmacSha256::new_from_slice(&prk).expect("HMAC accepts keys of any size");
+        expand.update(context);
+        expand.update(&[1_u8]);
+        let mut okm: [u8; 32] = hmac_sha256_wiped(expand);
+
+        let key: EncryptionKey = EncryptionKey::new(okm);
+        wipe(&mut prk);
+        wipe(&mut okm);
+
+        key
+    }
+
+    #[cfg(feature = "rand")]
+    pub fn random () -> EncryptionKey {
+        // 32 bytes from the operating system RNG, which is the only source
+        // fit for keys. The intermediate array is wiped as derive does.
+        let mut rng: OsRng = OsRng::new().unwrap_or_else(|error| panic!("OPE : cannot generate a random key. {}", error));
+        let mut bytes: [u8; 32] = [0_u8; 32];
+        rng.fill_bytes(&mut bytes);
+
+        let key: EncryptionKey = EncryptionKey::new(bytes);
+        wipe(&mut bytes);
+
+        key
+    }
+
+    pub fn as_bytes (&self) -> &[u8] {
+        &self.0
+    }
+}
+
+fn wipe (bytes: &mut [u8]) {
+    // Overwrite secret bytes before their memory is released. Volatile
+    // writes, followed by a fence, cannot be optimized away even though the
+    // bytes are never read again.
+    for byte in bytes.iter_mut() {
+        unsafe { ptr::write_volatile(byte, 0_u8) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+pub(crate) fn wipe_value<T> (value: &mut T) {
+    // Same as wipe, for the bytes of a value holding key material inline,
+    // such as a keyed HMAC. The value must not be used afterwards. Only
+    // values without drop glue are wiped : a Drop implementation could read
+    // the zeroed bytes, and the memory such values own elsewhere would be
+    // left anyway.
+    if mem::needs_drop::<T>() {
+        return;
+    }
+
+    // Raw writes rather than a &mut [u8], whose bytes would include padding
+    let bytes: *mut u8 = value as *mut T as *mut u8;
+    for offset in 0..mem::size_of::<T>() {
+        unsafe { ptr::write_volatile(bytes.add(offset), 0_u8) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+pub(crate) fn hmac_sha256_wiped (mut hmac: HmacSha256) -> [u8; 32] {
+    // Output of the HMAC, its state being wiped rather than dropped as is.
+    // finalize consumes the HMAC, so it is given a copy, which like the
+    // temporaries of the hash crates themselves is not wiped.
+    let output: [u8; 32] = hmac.clone().finalize().into_bytes().into();
+    wipe_value(&mut hmac);
+
+    output
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Never print the key, so that debugging an OPE cannot leak it
+        f.write_str("<redacted>")
+    }
+}
+
+impl Drop for EncryptionKey {
+    fn drop (&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::EncryptionKey;
+    use super::wipe_value;
+
+    #[test]
+    fn test_debug_redacted () {
+        let key: EncryptionKey = EncryptionKey::new("secret key");
+        assert_eq!(format!("{:?}", key), "<redacted>");
+        assert_eq!(key.as_bytes(), b"secret key");
+        assert_eq!(key.clone().as_bytes(), b"secret key");
+
+        let key: EncryptionKey = EncryptionKey::new([0xff_u8, 0x00_u8, 0xfe_u8]);
+        assert_eq!(format!("{:?}", key), "<redacted>");
+        assert_eq!(key.as_bytes(), &[0xff_u8, 0x00_u8, 0xfe_u8]);
+    }
+
+    #[test]
+    fn test_derive () {
+        // First 32 bytes of RFC 5869 test case 3, which has no salt and an
+        // empty info
+        let okm: [u8; 32] = [
+            0x8d, 0xa4, 0xe7, 0x75, 0xa5, 0x63, 0xc1, 0x8f, 0x71, 0x5f, 0x80, 0x2a, 0x06, 0x3c, 0x5a, 0x31,
+            0xb8, 0xa1, 0x1f, 0x5c, 0x5e, 0xe1, 0x87, 0x9e, 0xc3, 0x45, 0x4e, 0x5f, 0x3c, 0x73, 0x8d, 0x2d,
+        ];
+        assert_eq!(EncryptionKey::derive(&[0x0b_u8; 22], b"").as_bytes(), &okm);
+
+        assert_ne!(EncryptionKey::derive(b"master", b"users.age").as_bytes(), EncryptionKey::derive(b"master", b"users.salary").as_bytes());
+        assert_eq!(EncryptionKey::derive(b"master", b"users.age").as_bytes(), EncryptionKey::derive(b"master", b"users.age").as_bytes());
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_random () {
+        let key: EncryptionKey = EncryptionKey::random();
+        assert_eq!(key.as_bytes().len(), 32);
+        assert_ne!(key.as_bytes(), EncryptionKey::random().as_bytes());
+    }
+
+    #[test]
+    fn test_wipe_value () {
+        let mut words: ([u64; 4], u8) = ([u64::MAX; 4], 7_u8);
+        wipe_value(&mut words);
+        assert_eq!(words, ([0; 4], 0));
+
+        // Values with drop glue are left as they are
+        let mut bytes: Vec<u8> = vec![1, 2, 3];
+        wipe_value(&mut bytes);
+        assert_eq!(bytes, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,36 @@
+#![allow(clippy::upper_case_acronyms)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+mod analysis;
+mod domain;
+mod encoding;
+mod error;
+mod hgd;
+mod index;
+mod key;
+mod math;
+mod range;
+mod stat;
+mod tape;
+#[cfg(test)]
+mod testing;
+mod util;
+
+pub mod ope;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use ope::{ConfigError, DecryptError, EncryptError, HmacSha256AesCtr, HmacSha512AesCtr, OPE, OpeBuilder, OpeCompat, OpeConfig, OpeError, RangeError, RoundingMode, TapeCache, TapeGenerator, TapeStep, ValueRange, ValueRangeIter};
+pub use analysis::{GapStats, analyze_gaps};
+pub use domain::{OpeDomain, OpeInteger};
+pub use encoding::{EncodingError, MAX_STRING_LEN, decode_int_to_string, encode_string_to_int, string_range, try_decode_int_to_string, try_encode_string_to_int};
+pub use hgd::{CoinSource, HGD, HRUA_D1, HRUA_D2, HRUA_THRESHOLD, PRNG, ln_factorial};
+pub use index::OpeIndex;
+pub use stat::{sample_hgd, sample_uniform};
+pub use util::{HexError, bits_to_hex, hex_to_bits};
+#[cfg(feature = "rand")]
+pub use stat::sample_hgd_with_rng;
@@ -1,7 +0,0 @@
-mod hgd;
-mod stat;
-
-fn main() {
-
-}
-
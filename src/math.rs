@@ -0,0 +1,372 @@
+// Float functions which core does not provide, needed without std.
+//
+// They are implemented in portable Rust after FreeBSD's msun library (which
+// libm is also ported from). sqrt, floor, ceil and div_euclid are exact, ln
+// is within one ulp of the C library. The samplers and the partition call
+// them directly whether std is there or not, so that ciphertexts never
+// depend on the platform. Without std, FloatMath also provides them as
+// methods to the rest of the crate.
+#![cfg_attr(any(feature = "std", test), allow(dead_code))]
+
+#[cfg(not(any(feature = "std", test)))]
+pub trait FloatMath {
+    fn floor (self) -> Self;
+    fn ceil (self) -> Self;
+    fn ln (self) -> Self;
+    fn log2 (self) -> Self;
+}
+
+#[cfg(not(any(feature = "std", test)))]
+impl FloatMath for f64 {
+    fn floor (self) -> f64 {
+        floor(self)
+    }
+
+    fn ceil (self) -> f64 {
+        ceil(self)
+    }
+
+    fn ln (self) -> f64 {
+        ln(self)
+    }
+
+    fn log2 (self) -> f64 {
+        log2(self)
+    }
+}
+
+#[cfg(not(any(feature = "std", test)))]
+impl FloatMath for f32 {
+    fn floor (self) -> f32 {
+        floor(self as f64) as f32
+    }
+
+    fn ceil (self) -> f32 {
+        ceil(self as f64) as f32
+    }
+
+    fn ln (self) -> f32 {
+        ln(self as f64) as f32
+    }
+
+    fn log2 (self) -> f32 {
+        log2(self as f64) as f32
+    }
+}
+
+// 2^52, every f64 at least this large in magnitude is an integer
+const INTEGER_LIMIT: f64 = 4_503_599_627_370_496_f64;
+
+pub fn trunc (x: f64) -> f64 {
+    // Values below INTEGER_LIMIT fit in an i64, larger ones are integers
+    // already, and so are infinities. Zero keeps its sign.
+    if x.is_nan() || x.abs() >= INTEGER_LIMIT || x == 0_f64 {
+        return x;
+    }
+
+    (x as i64) as f64
+}
+
+pub fn floor (x: f64) -> f64 {
+    let truncated: f64 = trunc(x);
+    if truncated > x { truncated - 1_f64 } else { truncated }
+}
+
+pub fn ceil (x: f64) -> f64 {
+    let truncated: f64 = trunc(x);
+    if truncated < x { truncated + 1_f64 } else { truncated }
+}
+
+pub fn round (x: f64) -> f64 {
+    // Nearest integer, halves being rounded upwards, so -2.5 gives -2
+    // where f64::round gives -3
+    let floored: f64 = floor(x);
+    if x - floored >= 0.5_f64 { floored + 1_f64 } else { floored }
+}
+
+pub fn round_half_even (x: f64) -> f64 {
+    // Nearest integer, halves being rounded to the even one, so 2.5 gives 2
+    // and 3.5 gives 4
+    let floored: f64 = floor(x);
+    let difference: f64 = x - floored;
+
+    if difference > 0.5_f64 || (difference == 0.5_f64 && floor(floored / 2_f64) != floored / 2_f64) {
+        floored + 1_f64
+    } else {
+        floored
+    }
+}
+
+pub fn div_euclid (x: f64, rhs: f64) -> f64 {
+    // Same definition as f64::div_euclid
+    let quotient: f64 = trunc(x / rhs);
+    if x % rhs < 0_f64 {
+        return if rhs > 0_f64 { quotient - 1_f64 } else { quotient + 1_f64 };
+    }
+
+    quotient
+}
+
+pub fn sqrt (x: f64) -> f64 {
+
+    // Correctly rounded square root, from the integer square root of the
+    // mantissa. NaN, negative values, zeros and infinity are handled first.
+
+    if x.is_nan() || x < 0_f64 {
+        return f64::NAN;
+    }
+
+    if x == 0_f64 || x == f64::INFINITY {
+        return x;
+    }
+
+    // Write x = mantissa * 2^exponent with an integer mantissa, subnormal
+    // values included, and an even exponent
+    let bits: u64 = x.to_bits();
+    let biased: i64 = ((bits >> 52) & 0x7ff) as i64;
+    let fraction: u64 = bits & ((1_u64 << 52) - 1);
+
+    let (mut mantissa, mut exponent): (u128, i64) = if biased == 0 {
+        (fraction as u128, -1074)
+    } else {
+        ((fraction | (1_u64 << 52)) as u128, biased - 1075)
+    };
+
+    if exponent % 2 != 0 {
+        mantissa <<= 1;
+        exponent -= 1;
+    }
+
+    // Scale the mantissa up so that its root holds enough bits (at least 55)
+    // to be rounded, whatever the size of a subnormal mantissa
+    let shift: u32 = (126 - (128 - mantissa.leading_zeros())) & !1;
+    mantissa <<= shift;
+    exponent -= shift as i64;
+
+    let (mut root, remainder): (u128, u128) = isqrt(mantissa);
+
+    // A non zero remainder means the root is a little larger, which matters
+    // when rounding a root ending exactly halfway between two f64
+    if remainder != 0 {
+        root |= 1;
+    }
+
+    (root as f64) * power_of_two(exponent / 2)
+}
+
+fn isqrt (n: u128) -> (u128, u128) {
+    // Integer square root, one bit at a time, along with the remainder
+    let mut remainder: u128 = n;
+    let mut root: u128 = 0;
+    let mut bit: u128 = 1_u128 << 126;
+
+    while bit > n {
+        bit >>= 2;
+    }
+
+    while bit != 0 {
+        if remainder >= root + bit {
+            remainder -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+
+    (root, remainder)
+}
+
+fn power_of_two (exponent: i64) -> f64 {
+    // 2^exponent, for exponents giving a normal f64
+    f64::from_bits(((exponent + 1023) as u64) << 52)
+}
+
+#[allow(clippy::excessive_precision)]
+pub fn ln (x: f64) -> f64 {
+
+    // Natural logarithm, following msun's e_log.c : x is reduced to
+    // 2^k * (1 + f) with sqrt(2) / 2 < 1 + f < sqrt(2), and ln(1 + f) is
+    // evaluated with a polynomial in s = f / (2 + f).
+
+    const LN2_HI: f64 = 6.931_471_803_691_238_164_90e-01;
+    const LN2_LO: f64 = 1.908_214_929_270_587_700_02e-10;
+    const LG1: f64 = 6.666_666_666_666_735_130e-01;
+    const LG2: f64 = 3.999_999_999_940_941_908e-01;
+    const LG3: f64 = 2.857_142_874_366_239_149e-01;
+    const LG4: f64 = 2.222_219_843_214_978_396e-01;
+    const LG5: f64 = 1.818_357_216_161_805_012e-01;
+    const LG6: f64 = 1.531_383_769_920_937_332e-01;
+    const LG7: f64 = 1.479_819_860_511_658_591e-01;
+
+    let mut x: f64 = x;
+    let mut bits: u64 = x.to_bits();
+    let mut high: u32 = (bits >> 32) as u32;
+    let mut k: i32 = 0;
+
+    if high < 0x0010_0000 || (high >> 31) != 0 {
+        if bits << 1 == 0 {
+            return f64::NEG_INFINITY;
+        }
+        if high >> 31 != 0 {
+            return f64::NAN;
+        }
+
+        // Subnormal, scale it up by 2^54
+        k -= 54;
+        x *= power_of_two(54);
+        bits = x.to_bits();
+        high = (bits >> 32) as u32;
+    } else if high >= 0x7ff0_0000 {
+        return x;
+    } else if high == 0x3ff0_0000 && bits << 32 == 0 {
+        return 0_f64;
+    }
+
+    high += 0x3ff0_0000 - 0x3fe6_a09e;
+    k += (high >> 20) as i32 - 0x3ff;
+    high = (high & 0x000f_ffff) + 0x3fe6_a09e;
+    bits = ((high as u64) << 32) | (bits & 0xffff_ffff);
+    x = f64::from_bits(bits);
+
+    let f: f64 = x - 1_f64;
+    let hfsq: f64 = 0.5 * f * f;
+    let s: f64 = f / (2_f64 + f);
+    let z: f64 = s * s;
+    let w: f64 = z * z;
+    let t1: f64 = w * (LG2 + w * (LG4 + w * LG6));
+    let t2: f64 = z * (LG1 + w * (LG3 + w * (LG5 + w * LG7)));
+    let r: f64 = t2 + t1;
+    let dk: f64 = k as f64;
+
+    s * (hfsq + r) + dk * LN2_LO - hfsq + f + dk * LN2_HI
+}
+
+pub fn log2 (x: f64) -> f64 {
+    // Exact for powers of 2, which is what range sizes are compared to
+    let bits: u64 = x.to_bits();
+    if x > 0_f64 && x.is_finite() && bits & ((1_u64 << 52) - 1) == 0 && (bits >> 52) != 0 {
+        return ((bits >> 52) as i64 - 1023) as f64;
+    }
+
+    ln(x) / core::f64::consts::LN_2
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::ceil;
+    use super::div_euclid;
+    use super::floor;
+    use super::ln;
+    use super::log2;
+    use super::round;
+    use super::round_half_even;
+    use super::trunc;
+    use super::sqrt;
+
+    use crate::testing::lcg_next;
+
+    fn sample_values () -> Vec<f64> {
+        // Integers, halves, tiny, huge and subnormal values, along with a
+        // pseudo-random sweep of bit patterns
+        let mut values: Vec<f64> = vec![0.5, 1.0, 1.5, 2.0, 3.0, 7.0, 8.0, 1e-300, 1e300, 4.9e-324, 2.2e-308, f64::MAX, f64::MIN_POSITIVE];
+        values.extend((0..1000).map(|index| index as f64 + 0.25));
+
+        let mut seed: u64 = 42;
+        for _ in 0..100_000 {
+            let value: f64 = f64::from_bits(lcg_next(&mut seed) >> 1);
+            if value.is_finite() {
+                values.push(value);
+            }
+        }
+
+        values
+    }
+
+    #[test]
+    fn test_floor_ceil () {
+        for value in sample_values().iter().flat_map(|value| vec![*value, -*value]) {
+            assert_eq!(floor(value), value.floor(), "floor({})", value);
+            assert_eq!(ceil(value), value.ceil(), "ceil({})", value);
+        }
+
+        assert_eq!(floor(-0.5).to_bits(), (-1_f64).to_bits());
+        assert_eq!(floor(-0_f64).to_bits(), (-0_f64).to_bits());
+        assert_eq!(ceil(f64::INFINITY), f64::INFINITY);
+        assert!(floor(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn test_round () {
+        for value in sample_values().iter().flat_map(|value| vec![*value, -*value]) {
+            let expected: f64 = if value - value.floor() == 0.5 { value.ceil() } else { value.round() };
+            assert_eq!(round(value), expected, "round({})", value);
+        }
+
+        assert_eq!(round(5.5), 6_f64);
+        assert_eq!(round(-2.5), -2_f64);
+        assert_eq!(round(-2.6), -3_f64);
+        assert_eq!(round(0.49999999999999994), 0_f64);
+        assert_eq!(round(f64::INFINITY), f64::INFINITY);
+        assert!(round(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn test_round_half_even () {
+        for value in sample_values().iter().flat_map(|value| vec![*value, -*value]) {
+            assert_eq!(round_half_even(value), value.round_ties_even(), "round_half_even({})", value);
+            assert_eq!(trunc(value), value.trunc(), "trunc({})", value);
+        }
+
+        assert_eq!(round_half_even(2.5), 2_f64);
+        assert_eq!(round_half_even(3.5), 4_f64);
+        assert_eq!(round_half_even(-2.5), -2_f64);
+        assert_eq!(round_half_even(-3.5), -4_f64);
+        assert_eq!(round_half_even(2.6), 3_f64);
+        assert!(round_half_even(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn test_div_euclid () {
+        for value in (-1000..1000).map(|value| value as f64) {
+            assert_eq!(div_euclid(value, 2_f64), value.div_euclid(2_f64), "div_euclid({}, 2)", value);
+            assert_eq!(div_euclid(value, -3_f64), value.div_euclid(-3_f64), "div_euclid({}, -3)", value);
+        }
+    }
+
+    #[test]
+    fn test_sqrt () {
+        // Both are correctly rounded, so must be bit-identical
+        for value in sample_values() {
+            assert_eq!(sqrt(value).to_bits(), value.sqrt().to_bits(), "sqrt({})", value);
+        }
+
+        assert!(sqrt(-1_f64).is_nan());
+        assert_eq!(sqrt(f64::INFINITY), f64::INFINITY);
+        assert_eq!(sqrt(-0_f64).to_bits(), (-0_f64).to_bits());
+    }
+
+    #[test]
+    fn test_ln () {
+        for value in sample_values() {
+            let difference: i64 = ln(value).to_bits() as i64 - value.ln().to_bits() as i64;
+            assert!(difference.abs() <= 1, "ln({}) = {}, expected {}", value, ln(value), value.ln());
+        }
+
+        assert_eq!(ln(1_f64), 0_f64);
+        assert_eq!(ln(0_f64), f64::NEG_INFINITY);
+        assert!(ln(-1_f64).is_nan());
+        assert_eq!(ln(f64::INFINITY), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_log2 () {
+        for exponent in -1022..1024 {
+            assert_eq!(log2(2_f64.powi(exponent)), exponent as f64);
+        }
+
+        assert!((log2(1001_f64) - 1001_f64.log2()).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,2871 @@
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::marker::PhantomData;
+
+// Tapes are cached in a hash map when std is available
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as TapeMap;
+#[cfg(feature = "std")]
+use std::collections::HashMap as TapeMap;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "std")]
+use std::panic::{AssertUnwindSafe, catch_unwind};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::domain::{OpeDomain, OpeInteger, bounded_i128};
+pub use super::error::{ConfigError, DecryptError, EncryptError, OpeError};
+use super::key::{EncryptionKey, hmac_sha256_wiped};
+pub use super::range::{RangeError, ValueRange, ValueRangeIter};
+use super::stat::{sample_hgd, sample_hgd_i128, sample_uniform, sample_uniform_i128};
+pub use super::tape::{HmacSha256AesCtr, HmacSha512AesCtr, TapeGenerator};
+use super::tape::{COIN_BUDGET, TapeContext, decimal_tape_input, decimal_wide_tape_input, tape_input, wide_tape_input};
+#[cfg(not(any(feature = "std", test)))]
+use super::math::FloatMath;
+// Partition points are computed the same way with and without std
+use super::math::{ceil, floor, round, round_half_even, trunc};
+
+type HmacSha256 = Hmac<Sha256>;
+
+// 2^127, out_range bounds of encrypt_u128 must be below it for sizes to fit in an i128
+const WIDE_LIMIT: f64 = 170_141_183_460_469_231_731_687_303_715_884_105_728_f64;
+
+// 2^20, largest in_range encrypt_domain encrypts as a whole
+const DOMAIN_LIMIT: f64 = 1_048_576_f64;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OpeCompat {
+    // Encoding of values fed to the coin tapes. Native uses tape_input,
+    // Pyope formats values as pyope does so that ciphertexts match.
+    #[default]
+    Native,
+    Pyope,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RoundingMode {
+    // Integer a fractional plaintext is encrypted as by encrypt_rounded.
+    // Nearest rounds halves upwards, NearestEven to the even integer as
+    // banker's rounding does, and Truncate towards zero.
+    #[default]
+    Nearest,
+    NearestEven,
+    Floor,
+    Ceil,
+    Truncate,
+}
+
+impl RoundingMode {
+    pub fn round (self, value: f64) -> f64 {
+        match self {
+            RoundingMode::Nearest => round(value),
+            RoundingMode::NearestEven => round_half_even(value),
+            RoundingMode::Floor => floor(value),
+            RoundingMode::Ceil => ceil(value),
+            RoundingMode::Truncate => trunc(value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OpeConfig {
+    // Non-secret part of an OPE, which can be persisted and later combined
+    // with the encryption key to rebuild the same scheme
+    pub in_range: ValueRange,
+    pub out_range: ValueRange,
+}
+
+// Bytes of the payload of OpeConfig::to_bytes, the four range bounds
+const CONFIG_LEN: usize = 32;
+
+impl OpeConfig {
+    pub fn to_bytes (&self) -> Vec<u8> {
+
+        // Length of the payload as a little-endian u32, followed by
+        // in_range.start, in_range.end, out_range.start and out_range.end,
+        // each a little-endian f64. Like the serde representation, this
+        // holds no key.
+
+        let mut bytes: Vec<u8> = Vec::with_capacity(4 + CONFIG_LEN);
+        bytes.extend_from_slice(&(CONFIG_LEN as u32).to_le_bytes());
+
+        for bound in [self.in_range.start, self.in_range.end, self.out_range.start, self.out_range.end] {
+            bytes.extend_from_slice(&bound.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes (bytes: &[u8]) -> Result<OpeConfig, ConfigError> {
+
+        // Read back a configuration written by to_bytes. Ranges are checked
+        // as when deserializing with serde, and building an OPE from the
+        // configuration checks the rest.
+
+        if bytes.len() < 4 {
+            return Err(ConfigError::Truncated { len: bytes.len(), expected: 4 + CONFIG_LEN });
+        }
+
+        let (prefix, payload): (&[u8], &[u8]) = bytes.split_at(4);
+        let len: usize = u32::from_le_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+
+        if len != CONFIG_LEN {
+            return Err(ConfigError::InvalidLength(len));
+        }
+
+        if payload.len() < len {
+            return Err(ConfigError::Truncated { len: bytes.len(), expected: 4 + len });
+        }
+
+        if payload.len() > len {
+            return Err(ConfigError::InvalidLength(payload.len()));
+        }
+
+        let mut bounds: [f64; 4] = [0_f64; 4];
+        for (bound, chunk) in bounds.iter_mut().zip(payload.chunks_exact(8)) {
+            let mut word: [u8; 8] = [0; 8];
+            word.copy_from_slice(chunk);
+            *bound = f64::from_le_bytes(word);
+        }
+
+        Ok(OpeConfig {
+            in_range: ValueRange::try_new(bounds[0], bounds[1]).map_err(ConfigError::InvalidRange)?,
+            out_range: ValueRange::try_new(bounds[2], bounds[3]).map_err(ConfigError::InvalidRange)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OpeBuilder {
+    // Step by step construction of an OPE, naming each of its parameters.
+    // Everything is validated when calling build.
+    encryption_key: Option<EncryptionKey>,
+    in_bounds: Option<(f64, f64)>,
+    out_bounds: Option<(f64, f64)>,
+    compat: OpeCompat,
+    strict: bool,
+    iv: [u8; 16],
+    quantization: Option<f64>,
+}
+
+impl OpeBuilder {
+    pub fn new () -> OpeBuilder {
+        OpeBuilder::default()
+    }
+
+    pub fn key (mut self, encryption_key: impl AsRef<[u8]>) -> OpeBuilder {
+        self.encryption_key = Some(EncryptionKey::new(encryption_key));
+        self
+    }
+
+    pub fn in_range (mut self, start: f64, end: f64) -> OpeBuilder {
+        self.in_bounds = Some((start, end));
+        self
+    }
+
+    pub fn out_range (mut self, start: f64, end: f64) -> OpeBuilder {
+        self.out_bounds = Some((start, end));
+        self
+    }
+
+    pub fn compat (mut self, compat: OpeCompat) -> OpeBuilder {
+        self.compat = compat;
+        self
+    }
+
+    pub fn iv (mut self, iv: [u8; 16]) -> OpeBuilder {
+        // IV of the AES-CTR keystreams of the tapes, zero by default. Only
+        // needed to match implementations using another IV.
+        self.iv = iv;
+        self
+    }
+
+    pub fn strict (mut self, strict: bool) -> OpeBuilder {
+        // In strict mode, out_range must hold at least two ciphertexts per
+        // plaintext. With equal sizes, every plaintext has a single possible
+        // ciphertext and the ciphertexts hide nothing of the plaintexts.
+        self.strict = strict;
+        self
+    }
+
+    pub fn quantization (mut self, scale: f64) -> OpeBuilder {
+        // Scale of the plaintexts of encrypt_quantized, which are multiplied
+        // by it and rounded to the nearest integer. A scale of 100 encrypts
+        // amounts with 2 decimal places, in_range being given in cents.
+        self.quantization = Some(scale);
+        self
+    }
+
+    pub fn build (&self) -> Result<OPE, OpeError> {
+        let encryption_key: EncryptionKey = self.encryption_key.clone().ok_or(OpeError::MissingKey)?;
+        let (in_start, in_end) = self.in_bounds.ok_or(OpeError::MissingInRange)?;
+        let (out_start, out_end) = self.out_bounds.ok_or(OpeError::MissingOutRange)?;
+
+        let in_range: ValueRange = ValueRange::try_new(in_start, in_end).map_err(OpeError::InvalidRange)?;
+        let out_range: ValueRange = ValueRange::try_new(out_start, out_end).map_err(OpeError::InvalidRange)?;
+
+        let mut ope: OPE = OPE::from_ranges(encryption_key, &in_range, &out_range, self.compat, HmacSha256AesCtr::with_iv(self.iv))?;
+
+        if let Some(scale) = self.quantization {
+            if !scale.is_finite() || scale <= 0_f64 {
+                return Err(OpeError::InvalidQuantization(scale));
+            }
+            ope.scale = scale;
+        }
+
+        // Sizes are exact once the ranges are accepted
+        if self.strict && out_range.size() < 2_f64 * in_range.size() {
+            return Err(OpeError::InsufficientExpansion { in_size: in_range.size(), out_size: out_range.size() });
+        }
+
+        Ok(ope)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TapeStep {
+    // A step of the partition walked by encrypt_traced : the middle of
+    // out_range, the first coins drawn from its tape, and the resulting
+    // split point of in_range.
+    pub mid: f64,
+    pub coins: [u8; 128],
+    pub x: f64,
+}
+
+#[derive(Default)]
+pub struct TapeCache {
+    // First coins of the tapes computed while encrypting, keyed on the bit
+    // pattern of the value they were generated from, so that encrypt_batch_cached
+    // can reuse them across calls.
+    //
+    // Tapes depend on the encryption key, so the cache remembers which OPE
+    // filled it and starts over when used with another one.
+    owner: Option<[u8; 32]>,
+    tapes: TapeMap<u64, [u8; 128]>,
+}
+
+impl TapeCache {
+    pub fn new () -> TapeCache {
+        TapeCache::default()
+    }
+
+    pub fn len (&self) -> usize {
+        self.tapes.len()
+    }
+
+    pub fn is_empty (&self) -> bool {
+        self.tapes.is_empty()
+    }
+
+    pub fn clear (&mut self) {
+        self.owner = None;
+        self.tapes.clear();
+    }
+
+    fn bind (&mut self, owner: [u8; 32]) {
+        if self.owner != Some(owner) {
+            self.tapes.clear();
+            self.owner = Some(owner);
+        }
+    }
+
+    fn coins (&mut self, data: &f64, tape_gen: impl FnOnce() -> [u8; 128]) -> [u8; 128] {
+        *self.tapes.entry(data.to_bits()).or_insert_with(tape_gen)
+    }
+}
+
+#[derive(Debug)]
+pub struct OPE<T = f64, G: TapeGenerator = HmacSha256AesCtr> {
+    // Order-preserving encryption scheme as described by Boldyreva et al.
+    //
+    // Plaintexts are integers taken from in_range, ciphertexts are integers
+    // taken from out_range. Both ranges are inclusive and out_range must be
+    // at least as large as in_range.
+    //
+    // T is the plaintext type, either f64 or an OpeDomain integer type, and
+    // G the TapeGenerator coins are drawn from.
+    //
+    // The bounds are also kept as i128 values, for the integer partition of
+    // encrypt_u128 and the OpeDomain types. The ranges are only rounded from
+    // them for OpeDomain bounds beyond 2^53.
+    //
+    // tape_context is the generator keyed with encryption_key, set up once
+    // rather than for every tape.
+    //
+    // scale is the quantization of OpeBuilder, only used by the _quantized
+    // methods.
+    encryption_key: EncryptionKey,
+    tape_context: TapeContext<G::Context>,
+    in_range: ValueRange,
+    out_range: ValueRange,
+    in_bounds: (i128, i128),
+    out_bounds: (i128, i128),
+    compat: OpeCompat,
+    generator: G,
+    scale: f64,
+    domain: PhantomData<T>,
+}
+
+impl OPE {
+    pub fn new (encryption_key: impl AsRef<[u8]>, in_range: &ValueRange, out_range: &ValueRange) -> OPE {
+        OPE::try_new(encryption_key, in_range, out_range).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_new (encryption_key: impl AsRef<[u8]>, in_range: &ValueRange, out_range: &ValueRange) -> Result<OPE, OpeError> {
+        OPE::from_ranges(EncryptionKey::new(encryption_key), in_range, out_range, OpeCompat::Native, HmacSha256AesCtr::default())
+    }
+
+    pub fn new_wide (encryption_key: impl AsRef<[u8]>, in_range: &ValueRange, out_range: &ValueRange) -> OPE {
+        OPE::try_new_wide(encryption_key, in_range, out_range).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_new_wide (encryption_key: impl AsRef<[u8]>, in_range: &ValueRange, out_range: &ValueRange) -> Result<OPE, OpeError> {
+
+        // Same as try_new, out_range being allowed to hold up to 2^127 values
+        // for encrypt_u128. Beyond 2^53 values, encrypt and decrypt cannot be
+        // used, and try_encrypt reports it.
+
+        check_coin_budget(out_range)?;
+
+        if out_range.start < 0_f64 || out_range.end >= WIDE_LIMIT {
+            return Err(OpeError::InvalidWideRange(out_range.clone()));
+        }
+
+        if out_range.checked_size().is_some() {
+            return OPE::try_new(encryption_key, in_range, out_range);
+        }
+
+        // Such an out_range is larger than any in_range with an exact size
+        if in_range.checked_size().is_none() {
+            return Err(OpeError::InRangeTooLarge { in_size: in_range.end - in_range.start + 1_f64, out_size: out_range.end - out_range.start + 1_f64 });
+        }
+
+        Ok(OPE::from_parts_unchecked(encryption_key, in_range, out_range))
+    }
+
+    pub fn from_parts_unchecked (encryption_key: impl AsRef<[u8]>, in_range: &ValueRange, out_range: &ValueRange) -> OPE {
+
+        // Build an OPE without checking its ranges, for callers restoring one
+        // whose ranges were already accepted by try_new or try_new_wide, such
+        // as a deserialized OpeConfig.
+        //
+        // The caller must make sure that in_range holds at most as many values
+        // as out_range, and that out_range holds at most 2^53 values, or has
+        // bounds within [0, 2^127) for encrypt_u128. Memory stays safe either
+        // way, but encryption may panic or give ciphertexts that do not decrypt.
+
+        let encryption_key: EncryptionKey = EncryptionKey::new(encryption_key);
+        let tape_context: TapeContext<HmacSha256> = TapeContext(HmacSha256AesCtr::default().context(encryption_key.as_bytes()));
+
+        OPE {
+            encryption_key,
+            tape_context,
+            in_range: in_range.clone(),
+            out_range: out_range.clone(),
+            in_bounds: (in_range.start as i128, in_range.end as i128),
+            out_bounds: (out_range.start as i128, out_range.end as i128),
+            compat: OpeCompat::Native,
+            generator: HmacSha256AesCtr::default(),
+            scale: 1_f64,
+            domain: PhantomData,
+        }
+    }
+
+    pub fn builder () -> OpeBuilder {
+        OpeBuilder::new()
+    }
+
+    pub fn suggest_out_range (in_range: &ValueRange, expansion_bits: u32) -> ValueRange {
+
+        // out_range starting at 0 and holding 2^expansion_bits ciphertexts
+        // per plaintext of in_range. Each expansion bit makes ciphertexts less
+        // predictable from the plaintext order alone. Ciphertexts are f64, so
+        // the size of out_range is limited to 2^53.
+
+        let size: f64 = (0..expansion_bits.min(54)).fold(in_range.size(), |size, _| size * 2_f64);
+        let out_range: ValueRange = ValueRange::new(0_f64, size - 1_f64);
+
+        if out_range.checked_size().is_none() {
+            panic!("OPE : out_range size should not be greater than 2^53, {} expansion bits are too many for in_range {:?}.", expansion_bits, in_range);
+        }
+
+        out_range
+    }
+
+    pub fn from_config (config: &OpeConfig, encryption_key: impl AsRef<[u8]>) -> Result<OPE, OpeError> {
+        OPE::try_new(encryption_key, &config.in_range, &config.out_range)
+    }
+
+    pub fn derive (master_key: &[u8], context: &str, in_range: &ValueRange, out_range: &ValueRange) -> Result<OPE, OpeError> {
+        // Build an OPE keyed with a subkey of master_key, derived with
+        // HKDF-SHA256 from context (a column name for instance). Different
+        // contexts give unrelated keys and thus independent coin tapes, so
+        // that ciphertexts of a same plaintext cannot be matched across them.
+        OPE::from_ranges(EncryptionKey::derive(master_key, context.as_bytes()), in_range, out_range, OpeCompat::Native, HmacSha256AesCtr::default())
+    }
+
+    #[cfg(feature = "rand")]
+    pub fn ephemeral (in_range: &ValueRange, out_range: &ValueRange) -> OPE {
+
+        // Build an OPE keyed with a fresh random key, for a single session.
+        // The key cannot be read back, and its bytes are wiped on drop, as
+        // are the keyed HMAC states of the OPE.
+
+        OPE::from_ranges(EncryptionKey::random(), in_range, out_range, OpeCompat::Native, HmacSha256AesCtr::default())
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+}
+
+impl<G: TapeGenerator> OPE<f64, G> {
+    pub fn with_generator (encryption_key: impl AsRef<[u8]>, in_range: &ValueRange, out_range: &ValueRange, generator: G) -> Result<OPE<f64, G>, OpeError> {
+        // Same as try_new, drawing coins from the given generator instead of
+        // the default HMAC-SHA256 / AES-CTR one. Ciphertexts of OPEs with
+        // different generators have nothing in common.
+        OPE::from_ranges(EncryptionKey::new(encryption_key), in_range, out_range, OpeCompat::Native, generator)
+    }
+
+    pub fn encrypt (&self, plaintext: &f64) -> f64 {
+        self.try_encrypt(plaintext).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_encrypt (&self, plaintext: &f64) -> Result<f64, EncryptError> {
+
+        // Encrypt the given plaintext into an integer of out_range.
+
+        self.check_plaintext(plaintext)?;
+
+        // Ranges of a same size leave no room for randomness, each plaintext
+        // is mapped to the ciphertext of the same rank without walking the
+        // partition
+        if self.in_range.size() == self.out_range.size() {
+            return Ok(self.out_range.start + (*plaintext - self.in_range.start));
+        }
+
+        self.encrypt_recursive(plaintext, &self.in_range, &self.out_range, None, None)
+    }
+
+    pub fn encrypt_quantized (&self, value: &f64) -> f64 {
+        self.try_encrypt_quantized(value).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_encrypt_quantized (&self, value: &f64) -> Result<f64, EncryptError> {
+
+        // Encrypt a value of the grid set with OpeBuilder::quantization, the
+        // scaled value being rounded to the nearest integer of in_range.
+        // Values off the grid are encrypted as their nearest grid point.
+
+        self.try_encrypt(&floor(*value * self.scale + 0.5_f64))
+    }
+
+    pub fn encrypt_rounded (&self, plaintext: &f64, mode: RoundingMode) -> f64 {
+        self.try_encrypt_rounded(plaintext, mode).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_encrypt_rounded (&self, plaintext: &f64, mode: RoundingMode) -> Result<f64, EncryptError> {
+
+        // Encrypt the integer the given plaintext rounds to with mode, where
+        // try_encrypt rejects fractional plaintexts. Plaintexts rounding to
+        // the same integer share its ciphertext.
+
+        self.try_encrypt(&mode.round(*plaintext))
+    }
+
+    pub fn encrypt_saturating (&self, plaintext: &f64) -> f64 {
+
+        // Encrypt the given plaintext, values below in_range being encrypted
+        // as in_range.start and values above it as in_range.end, infinities
+        // included. Fractional values are rounded first, halves upwards, as
+        // by ValueRange::clamp. Meant for columns with no natural bound on
+        // one side.
+        //
+        // Order is only preserved loosely at the bounds : every value beyond
+        // a bound gets the same ciphertext as the bound itself, so they
+        // cannot be told apart, nor decrypted back to anything but the bound.
+
+        if plaintext.is_nan() {
+            panic!("{}", EncryptError::NonFinite(*plaintext));
+        }
+
+        self.encrypt(&self.in_range.clamp(*plaintext))
+    }
+
+    pub fn encrypt_u128 (&self, plaintext: u64) -> u128 {
+
+        // Encrypt the given plaintext with integer arithmetic on out_range, so
+        // that it can be far larger than 2^53 (up to 2^127) and ciphertexts
+        // still be exact. Such an OPE is built with try_new_wide, and encrypt
+        // cannot be used with it. Both give the same ciphertexts otherwise.
+        //
+        // Hypergeometric samples are still computed on f64 values, rounding
+        // the sizes of out_range halves beyond 2^53.
+
+        if self.out_range.start < 0_f64 || self.out_range.end >= WIDE_LIMIT {
+            panic!("{}", OpeError::InvalidWideRange(self.out_range.clone()));
+        }
+
+        let plaintext: i128 = plaintext as i128;
+        if plaintext < self.in_bounds.0 || plaintext > self.in_bounds.1 {
+            panic!("{}", EncryptError::OutOfRange { plaintext: plaintext as f64, in_range: self.in_range.clone() });
+        }
+
+        self.encrypt_exact(plaintext) as u128
+    }
+
+    #[cfg(feature = "std")]
+    pub fn safe_encrypt (&self, plaintext: &f64) -> Result<f64, EncryptError> {
+
+        // Same as try_encrypt, which already reports every invalid plaintext
+        // as an error, also catching any panic left during encryption, as
+        // the ffi module does. Meant for untrusted input, where a bug should
+        // not bring the service down. The panic message is still printed by
+        // the panic hook.
+
+        catch_unwind(AssertUnwindSafe(|| self.try_encrypt(plaintext))).unwrap_or(Err(EncryptError::Panicked))
+    }
+
+    pub fn encrypt_batch (&self, plaintexts: &[f64]) -> Result<Vec<f64>, EncryptError> {
+
+        // Encrypt all the given plaintexts, in order. Every plaintext is
+        // checked before encrypting any of them, so that an invalid one
+        // fails the whole batch upfront.
+
+        for plaintext in plaintexts.iter() {
+            self.check_plaintext(plaintext)?;
+        }
+
+        plaintexts.iter().map(|plaintext| self.encrypt_recursive(plaintext, &self.in_range, &self.out_range, None, None)).collect()
+    }
+
+    pub fn encrypt_batch_cached (&self, plaintexts: &[f64], cache: &mut TapeCache) -> Result<Vec<f64>, EncryptError> {
+
+        // Same as encrypt_batch, reusing the tapes already in the cache.
+        // Nearby plaintexts walk through the same partition points, whose
+        // tapes are then only computed once.
+
+        for plaintext in plaintexts.iter() {
+            self.check_plaintext(plaintext)?;
+        }
+
+        cache.bind(self.cache_owner());
+        plaintexts.iter().map(|plaintext| self.encrypt_recursive(plaintext, &self.in_range, &self.out_range, Some(cache), None)).collect()
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn encrypt_par (&self, plaintexts: &[f64]) -> Vec<f64> where G: Sync, G::Context: Sync {
+
+        // Same as encrypting every plaintext in turn, spread over rayon's
+        // thread pool. Encryption only reads the OPE, so plaintexts can be
+        // encrypted independently, and results are kept in order.
+
+        plaintexts.par_iter().map(|plaintext| self.encrypt(plaintext)).collect()
+    }
+
+    pub fn encrypt_iter<'a, I: Iterator<Item = f64> + 'a> (&'a self, plaintexts: I) -> impl Iterator<Item = Result<f64, EncryptError>> + 'a {
+
+        // Encrypt the given plaintexts lazily, one at a time as the iterator
+        // is consumed. Unlike encrypt_batch, an invalid plaintext only gives
+        // an error in its own position, the others are still encrypted.
+
+        plaintexts.map(move |plaintext| self.try_encrypt(&plaintext))
+    }
+
+    pub fn encrypt_traced (&self, plaintext: &f64) -> (f64, Vec<TapeStep>) {
+
+        // Encrypt the given plaintext, also returning every step of the
+        // partition walked, so that it can be compared with other
+        // implementations. Meant for debugging, use encrypt otherwise.
+
+        if let Err(error) = self.check_plaintext(plaintext) {
+            panic!("{}", error);
+        }
+
+        let mut trace: Vec<TapeStep> = Vec::new();
+        let ciphertext: f64 = self.encrypt_recursive(plaintext, &self.in_range, &self.out_range, None, Some(&mut trace))
+            .unwrap_or_else(|error| panic!("{}", error));
+
+        (ciphertext, trace)
+    }
+
+    pub fn decrypt (&self, ciphertext: &f64) -> f64 {
+        self.try_decrypt(ciphertext).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_decrypt (&self, ciphertext: &f64) -> Result<f64, DecryptError> {
+
+        // Decrypt the given ciphertext back into an integer of in_range. A
+        // ciphertext of out_range may still match no plaintext, which is only
+        // found out while walking the partition.
+
+        self.check_ciphertext(ciphertext, &self.out_range)?;
+        self.decrypt_recursive(ciphertext, &self.in_range, &self.out_range)
+    }
+
+    pub fn encrypt_to_bytes (&self, plaintext: &f64) -> Vec<u8> {
+        self.try_encrypt_to_bytes(plaintext).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_encrypt_to_bytes (&self, plaintext: &f64) -> Result<Vec<u8>, EncryptError> {
+
+        // Ciphertext as its offset from out_range.start, written in big-endian
+        // with as many bytes as the largest offset of out_range needs. Every
+        // ciphertext of this OPE then has the same length, and their bytes
+        // sort in the same order as the ciphertexts, negative ones included.
+
+        let ciphertext: f64 = self.try_encrypt(plaintext)?;
+        let offset: u64 = (ciphertext - self.out_range.start) as u64;
+
+        Ok(offset.to_be_bytes()[8 - self.ciphertext_width()..].to_vec())
+    }
+
+    pub fn decrypt_from_bytes (&self, bytes: &[u8]) -> f64 {
+        self.try_decrypt_from_bytes(bytes).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_decrypt_from_bytes (&self, bytes: &[u8]) -> Result<f64, DecryptError> {
+
+        // Decrypt a ciphertext written by encrypt_to_bytes
+
+        if self.out_range.checked_size().is_none() {
+            return Err(DecryptError::WideOutRange(self.out_range.clone()));
+        }
+
+        let width: usize = self.ciphertext_width();
+        if bytes.len() != width {
+            return Err(DecryptError::InvalidLength { len: bytes.len(), expected: width });
+        }
+
+        let mut word: [u8; 8] = [0; 8];
+        word[8 - width..].copy_from_slice(bytes);
+
+        self.try_decrypt(&(self.out_range.start + u64::from_be_bytes(word) as f64))
+    }
+
+    fn ciphertext_width (&self) -> usize {
+        // Bytes needed for the largest offset of out_range, at least one.
+        // Offsets are below 2^53, so this is at most 7.
+        let max_offset: u64 = (self.out_range.size() - 1_f64) as u64;
+        let bits: u32 = u64::BITS - max_offset.leading_zeros();
+
+        (bits.max(1) as usize).div_ceil(8)
+    }
+
+    pub fn plaintext_distance (&self, c1: &f64, c2: &f64) -> f64 {
+        self.try_plaintext_distance(c1, c2).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_plaintext_distance (&self, c1: &f64, c2: &f64) -> Result<f64, DecryptError> {
+
+        // Number of plaintexts from the plaintext of c1 to the plaintext of
+        // c2, 1 for consecutive plaintexts and 0 for equal ciphertexts. Both
+        // ciphertexts are checked against out_range before decrypting either.
+
+        self.check_ciphertext(c1, &self.out_range)?;
+        self.check_ciphertext(c2, &self.out_range)?;
+
+        Ok((self.try_decrypt(c2)? - self.try_decrypt(c1)?).abs())
+    }
+
+    pub fn decrypt_batch (&self, ciphertexts: &[f64]) -> Result<Vec<f64>, DecryptError> {
+
+        // Decrypt all the given ciphertexts, in order. Like encrypt_batch,
+        // every ciphertext is checked against out_range first, a ciphertext
+        // matching no plaintext still only being found out when decrypted.
+
+        for ciphertext in ciphertexts.iter() {
+            self.check_ciphertext(ciphertext, &self.out_range)?;
+        }
+
+        ciphertexts.iter().map(|ciphertext| self.decrypt_recursive(ciphertext, &self.in_range, &self.out_range)).collect()
+    }
+
+    #[cfg(feature = "rayon")]
+    pub fn decrypt_par (&self, ciphertexts: &[f64]) -> Vec<f64> where G: Sync, G::Context: Sync {
+
+        // Same as decrypting every ciphertext in turn, spread over rayon's
+        // thread pool, as encrypt_par does
+
+        ciphertexts.par_iter().map(|ciphertext| self.decrypt(ciphertext)).collect()
+    }
+
+    pub fn decrypt_checked (&self, ciphertext: &f64) -> Result<f64, DecryptError> {
+
+        // Same as try_decrypt, then encrypting the plaintext found again, so
+        // that a corrupted ciphertext is reported rather than decrypted into
+        // a nearby plaintext. try_decrypt already samples the ciphertext of
+        // the plaintext it finds, this also covers the way there.
+
+        let plaintext: f64 = self.try_decrypt(ciphertext)?;
+
+        match self.try_encrypt(&plaintext) {
+            Ok(expected) if expected == *ciphertext => Ok(plaintext),
+            _ => Err(DecryptError::NoMatch(*ciphertext)),
+        }
+    }
+
+    pub fn decrypt_with_range (&self, ciphertext: &f64, legacy_out_range: &ValueRange) -> f64 {
+        self.try_decrypt_with_range(ciphertext, legacy_out_range).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_decrypt_with_range (&self, ciphertext: &f64, legacy_out_range: &ValueRange) -> Result<f64, DecryptError> {
+
+        // Same as try_decrypt, for a ciphertext encrypted with the same key and
+        // in_range but another out_range, typically before it was widened. The
+        // partition only depends on the ranges, so walking it with the legacy
+        // out_range finds the plaintext back without another OPE.
+
+        self.check_ciphertext(ciphertext, legacy_out_range)?;
+
+        if self.in_range.size() > legacy_out_range.size() {
+            return Err(DecryptError::InRangeTooLarge { in_size: self.in_range.size(), out_size: legacy_out_range.size() });
+        }
+
+        self.decrypt_recursive(ciphertext, &self.in_range, legacy_out_range)
+    }
+
+    pub fn decrypt_quantized (&self, ciphertext: &f64) -> f64 {
+        self.try_decrypt_quantized(ciphertext).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_decrypt_quantized (&self, ciphertext: &f64) -> Result<f64, DecryptError> {
+        // Decrypt a ciphertext of encrypt_quantized back onto its grid
+        Ok(self.try_decrypt(ciphertext)? / self.scale)
+    }
+
+    pub fn decrypt_quantile (&self, sorted_ciphertexts: &[f64], q: f64) -> f64 {
+
+        // Decrypt the ciphertext at quantile q (between 0 and 1) of the given
+        // ciphertexts, taking the nearest rank. Encryption preserves the order,
+        // so this is the plaintext at quantile q, and only one value needs to
+        // be decrypted. Ciphertexts must already be sorted in ascending order.
+
+        if sorted_ciphertexts.is_empty() {
+            panic!("OPE : cannot take a quantile of no ciphertexts.");
+        }
+
+        if !(0_f64..=1_f64).contains(&q) {
+            panic!("OPE : quantile ({}) should be between 0 and 1.", q);
+        }
+
+        let index: usize = (q * (sorted_ciphertexts.len() - 1) as f64 + 0.5_f64).floor() as usize;
+        self.decrypt(&sorted_ciphertexts[index])
+    }
+
+    pub fn encrypt_range_query (&self, lo: &f64, hi: &f64) -> (f64, f64) {
+        self.try_encrypt_range_query(lo, hi).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_encrypt_range_query (&self, lo: &f64, hi: &f64) -> Result<(f64, f64), EncryptError> {
+
+        // Ciphertext bounds of the plaintexts from lo to hi, both included.
+        // Encryption preserves the order, so a column encrypted with this OPE
+        // is queried with BETWEEN the returned bounds.
+
+        self.check_plaintext(lo)?;
+        self.check_plaintext(hi)?;
+
+        if lo > hi {
+            return Err(EncryptError::InvalidQuery { lo: *lo, hi: *hi });
+        }
+
+        Ok((self.try_encrypt(lo)?, self.try_encrypt(hi)?))
+    }
+
+    pub fn cipher_bounds (&self) -> (f64, f64) {
+
+        // Ciphertexts of in_range.start and in_range.end. Encryption
+        // preserves the order, so every other ciphertext lies between them.
+        // These are not out_range's bounds, which are seldom reached.
+
+        self.encrypt_range_query(&self.in_range.start, &self.in_range.end)
+    }
+
+    pub fn shard_of (&self, ciphertext: &f64, num_shards: usize) -> usize {
+
+        // Index of the shard holding the given ciphertext, out_range being
+        // split into num_shards contiguous bands of equal sizes, up to one
+        // value. Encryption preserves the order, so a range query only hits
+        // the shards from shard_of(lo) to shard_of(hi).
+
+        if num_shards == 0 {
+            panic!("OPE : num_shards should be greater than 0.");
+        }
+
+        self.check_ciphertext(ciphertext, &self.out_range).unwrap_or_else(|error| panic!("{}", error));
+
+        // Below 2^53, both the offset and the size are exact integers, and
+        // so is their product with num_shards as a u128
+        let offset: u128 = (*ciphertext - self.out_range.start) as u128;
+        let size: u128 = self.out_range.size() as u128;
+
+        (offset * num_shards as u128 / size) as usize
+    }
+
+    pub fn encrypt_domain (&self) -> Result<Vec<(f64, f64)>, EncryptError> {
+
+        // Every (plaintext, ciphertext) pair of in_range, in plaintext order,
+        // and so in ciphertext order too, to precompute an encrypted index.
+        // in_ranges of more than DOMAIN_LIMIT plaintexts are rejected rather
+        // than filling the memory.
+
+        let in_size: f64 = self.in_range.size();
+        if in_size > DOMAIN_LIMIT {
+            return Err(EncryptError::DomainTooLarge { in_size, limit: DOMAIN_LIMIT });
+        }
+
+        // Consecutive plaintexts share most of their partition points
+        let plaintexts: Vec<f64> = self.in_range.iter().collect();
+        let ciphertexts: Vec<f64> = self.encrypt_batch_cached(&plaintexts, &mut TapeCache::new())?;
+
+        Ok(plaintexts.into_iter().zip(ciphertexts).collect())
+    }
+
+    pub fn leakage_estimate (&self, plaintext_histogram: &[(f64, u64)]) -> Result<f64, EncryptError> {
+
+        // Rough score, from 0 to 1, of how much ciphertexts of plaintexts
+        // following the given (plaintext, count) histogram give away through
+        // their frequencies. Encryption is deterministic and injective, so
+        // each ciphertext appears exactly as often as its plaintext : the
+        // score is 1 minus the entropy of the plaintext frequencies over that
+        // of a uniform distribution on in_range, and needs no encryption.
+        // Plaintexts uniform over the whole of in_range score 0, uniform over
+        // part of it more than 0, and a single repeated one 1.
+        //
+        // Entries are checked as plaintexts to encrypt would be, the first
+        // invalid one being returned as an error.
+        //
+        // This is a heuristic diagnostic, it ignores what order itself leaks
+        // and is no security measure.
+
+        for (plaintext, _) in plaintext_histogram.iter() {
+            self.check_plaintext(plaintext)?;
+        }
+
+        let mut plaintexts: Vec<(f64, u64)> = plaintext_histogram.to_vec();
+        plaintexts.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let total: u64 = plaintexts.iter().map(|(_, count)| count).sum();
+        if total == 0 {
+            panic!("OPE : the plaintext histogram should count at least one plaintext.");
+        }
+
+        let in_bits: f64 = (self.in_range.end - self.in_range.start + 1_f64).log2();
+        if in_bits == 0_f64 {
+            return Ok(0_f64);
+        }
+
+        // Counts of a same plaintext, given in several entries, add up
+        let mut entropy: f64 = 0_f64;
+        for group in plaintexts.chunk_by(|a, b| a.0 == b.0) {
+            let p: f64 = group.iter().map(|(_, count)| count).sum::<u64>() as f64 / total as f64;
+            if p > 0_f64 {
+                entropy -= p * p.log2();
+            }
+        }
+
+        Ok((1_f64 - entropy / in_bits).clamp(0_f64, 1_f64))
+    }
+
+    pub fn rotation_plan<'a> (&'a self, new_key: &[u8]) -> impl Iterator<Item = (f64, f64)> + 'a where G: Clone {
+
+        // Every (old ciphertext, new ciphertext) pair of in_range, in plaintext
+        // order, the new ciphertexts being those of an OPE with the same ranges
+        // keyed with new_key. A column can then be migrated in batches by
+        // looking its ciphertexts up in the pairs, without handling plaintexts.
+        //
+        // The whole of in_range is enumerated, so this only suits small
+        // in_ranges. Use reencrypt on each stored ciphertext otherwise.
+
+        let new: OPE<f64, G> = OPE::from_ranges(EncryptionKey::new(new_key), &self.in_range, &self.out_range, self.compat, self.generator.clone())
+            .unwrap_or_else(|error| panic!("{}", error));
+
+        self.in_range.iter().map(move |plaintext| (self.encrypt(&plaintext), new.encrypt(&plaintext)))
+    }
+
+    pub fn is_valid_ciphertext (&self, ciphertext: &f64) -> bool {
+
+        // Cheap check that the given value could be a ciphertext of this OPE :
+        // a finite integer of out_range. It says nothing of whether some
+        // plaintext actually encrypts to it, which only decrypt can tell.
+
+        ciphertext.is_finite() && ciphertext % 1_f64 == 0_f64 && self.out_range.contains(ciphertext)
+    }
+
+    pub fn reencrypt<H: TapeGenerator> (&self, ciphertext: &f64, new: &OPE<f64, H>) -> f64 {
+
+        // Move a ciphertext of this OPE to the new one, typically built with
+        // a wider out_range, by decrypting and encrypting it again. The
+        // plaintext never leaves this function, but both OPEs, and so both
+        // keys, must be held by the same trusted party.
+
+        new.encrypt(&self.decrypt(ciphertext))
+    }
+
+    pub fn compare_cipher (&self, a: &f64, b: &f64) -> Ordering {
+
+        // Compare two ciphertexts of this OPE, which is the same as comparing
+        // their plaintexts. Ciphertexts of OPEs with different keys or ranges
+        // are not ordered with each other, so both must be in out_range.
+
+        for ciphertext in [a, b].iter() {
+            if !(self.out_range.contains(ciphertext)) {
+                panic!("OPE : ciphertext ({}) should be in out_range {:?}.", ciphertext, self.out_range);
+            }
+        }
+
+        // Values of out_range are never NaN
+        a.partial_cmp(b).unwrap()
+    }
+
+}
+
+impl<T, G: TapeGenerator> OPE<T, G> {
+    fn from_ranges (encryption_key: EncryptionKey, in_range: &ValueRange, out_range: &ValueRange, compat: OpeCompat, generator: G) -> Result<OPE<T, G>, OpeError> {
+
+        // Ciphertexts are f64 values, so out_range must have an exact size,
+        // see try_new_wide otherwise. An in_range without one is larger still.
+        check_coin_budget(out_range)?;
+        let out_size: f64 = out_range.checked_size().ok_or_else(|| OpeError::OutRangeTooLarge(out_range.clone()))?;
+
+        match in_range.checked_size() {
+            Some(in_size) if in_size <= out_size => (),
+            _ => return Err(OpeError::InRangeTooLarge { in_size: in_range.end - in_range.start + 1_f64, out_size }),
+        }
+
+        let tape_context: TapeContext<G::Context> = TapeContext(generator.context(encryption_key.as_bytes()));
+
+        Ok(OPE {
+            encryption_key,
+            tape_context,
+            in_range: in_range.clone(),
+            out_range: out_range.clone(),
+            in_bounds: (in_range.start as i128, in_range.end as i128),
+            out_bounds: (out_range.start as i128, out_range.end as i128),
+            compat,
+            generator,
+            scale: 1_f64,
+            domain: PhantomData,
+        })
+    }
+
+    pub fn config (&self) -> OpeConfig {
+        // The encryption key is deliberately left out of the configuration
+        OpeConfig {
+            in_range: self.in_range.clone(),
+            out_range: self.out_range.clone(),
+        }
+    }
+
+    pub fn is_compatible_with (&self, other: &OPE<T, G>) -> bool {
+
+        // Whether ciphertexts of both OPEs can be compared with each other,
+        // that is whether they have the same ranges and tapes. Keys are
+        // compared through an HMAC of a fixed value rather than directly,
+        // along with the compatibility mode which also changes the tapes.
+
+        self.in_bounds == other.in_bounds && self.out_bounds == other.out_bounds && self.cache_owner() == other.cache_owner()
+    }
+
+    pub fn config_fingerprint (&self) -> [u8; 32] {
+
+        // Stable identifier of this OPE, to namespace caches of its results.
+        // This is an HMAC of the ranges keyed with the encryption key, which
+        // it does not reveal. OPEs have the same fingerprint exactly when
+        // is_compatible_with holds between them, up to HMAC collisions.
+
+        let mut hmac = HmacSha256::new_from_slice(self.encryption_key.as_bytes()).expect("HMAC accepts keys of any size");
+        hmac.update(b"config fingerprint");
+
+        for bound in [self.in_bounds.0, self.in_bounds.1, self.out_bounds.0, self.out_bounds.1] {
+            hmac.update(&bound.to_le_bytes());
+        }
+
+        // Compatibility mode and generator, as in cache_owner
+        hmac.update(&self.cache_owner());
+
+        hmac_sha256_wiped(hmac)
+    }
+
+    pub fn max_depth (&self) -> u32 {
+        // Number of halvings needed to isolate a plaintext of in_range, had
+        // in_range been split evenly at each step. Hypergeometric splits are
+        // only balanced on average, so a given walk may be a little deeper.
+        (self.in_range.end - self.in_range.start + 1_f64).log2().ceil() as u32
+    }
+
+    pub fn out_range_bits (&self) -> f64 {
+        // Number of bits needed to represent any ciphertext of out_range
+        (self.out_range.end - self.out_range.start + 1_f64).log2()
+    }
+
+    fn check_plaintext (&self, plaintext: &f64) -> Result<(), EncryptError> {
+        // NaN and infinities are never in in_range, but are reported as such
+        // rather than as out of range
+        if !plaintext.is_finite() {
+            return Err(EncryptError::NonFinite(*plaintext));
+        }
+
+        // Tapes are seeded with the integer part of values, so a fractional
+        // plaintext would share the coins of its integer part
+        if *plaintext != plaintext.floor() {
+            return Err(EncryptError::NotInteger(*plaintext));
+        }
+
+        // Only OPEs built with try_new_wide may get there
+        if self.out_range.checked_size().is_none() {
+            return Err(EncryptError::WideOutRange(self.out_range.clone()));
+        }
+
+        if !(self.in_range.contains(plaintext)) {
+            return Err(EncryptError::OutOfRange { plaintext: *plaintext, in_range: self.in_range.clone() });
+        }
+
+        Ok(())
+    }
+
+    fn encrypt_recursive (&self, plaintext: &f64, in_range: &ValueRange, out_range: &ValueRange, mut cache: Option<&mut TapeCache>, mut trace: Option<&mut Vec<TapeStep>>) -> Result<f64, EncryptError> {
+
+        // Both ranges are split in two at each step : out_range is split at its
+        // middle, and in_range at the point given by the hypergeometric sample
+        // seeded with the middle of out_range. The recursion then goes on with
+        // the halves containing the plaintext, until a single plaintext is left
+        // which is mapped uniformly onto what remains of out_range.
+        //
+        // A single value in_range goes straight to this last step, its only
+        // plaintext getting a ciphertext drawn from the whole of out_range.
+        // There is then no order to preserve, nor any to leak.
+        //
+        // The recursion is unrolled into a loop so that the stack does not grow
+        // with the size of the ranges.
+
+        let mut in_range: ValueRange = in_range.clone();
+        let mut out_range: ValueRange = out_range.clone();
+
+        while in_range.size() > 1_f64 {
+            let out_size: f64 = out_range.size();
+
+            let out_edge: f64 = out_range.start - 1_f64;
+            let mid: f64 = out_edge + ceil(out_size / 2_f64);
+
+            let (coins, x): ([u8; 128], f64) = self.split_point(&in_range, &out_range, &mid, cache.as_deref_mut());
+
+            // Both ranges have integer bounds, so mid is one too, and so is the
+            // hypergeometric sample x. Halves built from them stay integral.
+            debug_assert!(mid == mid.floor() && x == x.floor(), "OPE : partition points should be integers. Found mid = {}, x = {}", mid, x);
+
+            if let Some(steps) = trace.as_mut() {
+                steps.push(TapeStep { mid, coins, x });
+            }
+
+            let (lower_out, upper_out): (ValueRange, ValueRange) = out_range.try_split_at(mid).map_err(EncryptError::Internal)?;
+            let (lower_in, upper_in): (Option<ValueRange>, Option<ValueRange>) = partition(&in_range, x).map_err(EncryptError::Internal)?;
+
+            // The half holding the plaintext is never empty
+            let empty = || EncryptError::Internal(RangeError::InvalidSplit { mid: x, start: in_range.start, end: in_range.end });
+
+            if *plaintext <= x {
+                in_range = lower_in.ok_or_else(empty)?;
+                out_range = lower_out;
+            } else {
+                in_range = upper_in.ok_or_else(empty)?;
+                out_range = upper_out;
+            }
+        }
+
+        let coins: [u8; 128] = match cache {
+            Some(cache) => cache.coins(plaintext, || self.tape_gen(plaintext)),
+            None => self.tape_gen(plaintext),
+        };
+        Ok(sample_uniform(&out_range, &coins))
+    }
+
+    fn check_ciphertext (&self, ciphertext: &f64, out_range: &ValueRange) -> Result<(), DecryptError> {
+        // NaN is never in out_range either
+        if !(out_range.contains(ciphertext)) {
+            return Err(DecryptError::OutOfRange { ciphertext: *ciphertext, out_range: out_range.clone() });
+        }
+
+        if *ciphertext != ciphertext.floor() {
+            return Err(DecryptError::NotInteger(*ciphertext));
+        }
+
+        if out_range.checked_size().is_none() {
+            return Err(DecryptError::WideOutRange(out_range.clone()));
+        }
+
+        Ok(())
+    }
+
+    fn decrypt_recursive (&self, ciphertext: &f64, in_range: &ValueRange, out_range: &ValueRange) -> Result<f64, DecryptError> {
+
+        // Walk the same partition as encrypt_recursive, this time choosing the
+        // half of out_range containing the ciphertext.
+
+        let mut in_range: ValueRange = in_range.clone();
+        let mut out_range: ValueRange = out_range.clone();
+
+        while in_range.size() > 1_f64 {
+            let out_size: f64 = out_range.size();
+
+            let out_edge: f64 = out_range.start - 1_f64;
+            let mid: f64 = out_edge + ceil(out_size / 2_f64);
+
+            let (_, x): ([u8; 128], f64) = self.split_point(&in_range, &out_range, &mid, None);
+            debug_assert!(mid == mid.floor() && x == x.floor(), "OPE : partition points should be integers. Found mid = {}, x = {}", mid, x);
+
+            // A half of out_range may hold no plaintext at all, in which case
+            // the ciphertext cannot be valid
+            let invalid = || DecryptError::NoMatch(*ciphertext);
+
+            let (lower_out, upper_out): (ValueRange, ValueRange) = out_range.try_split_at(mid).map_err(DecryptError::Internal)?;
+            let (lower_in, upper_in): (Option<ValueRange>, Option<ValueRange>) = partition(&in_range, x).map_err(DecryptError::Internal)?;
+
+            if *ciphertext <= mid {
+                in_range = lower_in.ok_or_else(invalid)?;
+                out_range = lower_out;
+            } else {
+                in_range = upper_in.ok_or_else(invalid)?;
+                out_range = upper_out;
+            }
+        }
+
+        let coins: [u8; 128] = self.tape_gen(&in_range.start);
+        let sampled_ciphertext: f64 = sample_uniform(&out_range, &coins);
+
+        if sampled_ciphertext != *ciphertext {
+            return Err(DecryptError::NoMatch(*ciphertext));
+        }
+
+        Ok(in_range.start)
+    }
+
+    fn encrypt_exact (&self, plaintext: i128) -> i128 {
+
+        // Same partition as encrypt_recursive, with integer arithmetic on the
+        // bounds so that they can be far beyond 2^53. The plaintext must be
+        // within in_bounds.
+
+        let ((mut in_start, mut in_end), (mut out_start, mut out_end)): ((i128, i128), (i128, i128)) = (self.in_bounds, self.out_bounds);
+
+        while in_start < in_end {
+            let (mid, x): (i128, i128) = self.split_point_exact((in_start, in_end), (out_start, out_end));
+
+            if plaintext <= x {
+                in_end = x;
+                out_end = mid;
+            } else {
+                in_start = x + 1;
+                out_start = mid + 1;
+            }
+        }
+
+        let coins: [u8; 128] = G::next_coins(&mut self.wide_tape(plaintext));
+        sample_uniform_i128(out_start, out_end, &coins)
+    }
+
+    fn decrypt_exact (&self, ciphertext: i128) -> Result<i128, DecryptError> {
+
+        // Same walk as decrypt_recursive, with the arithmetic of encrypt_exact.
+        // The ciphertext must be within out_bounds.
+
+        let ((mut in_start, mut in_end), (mut out_start, mut out_end)): ((i128, i128), (i128, i128)) = (self.in_bounds, self.out_bounds);
+
+        while in_start < in_end {
+            let (mid, x): (i128, i128) = self.split_point_exact((in_start, in_end), (out_start, out_end));
+
+            // As in decrypt_recursive, an empty half means an invalid ciphertext
+            if ciphertext <= mid {
+                in_end = x;
+                out_end = mid;
+            } else {
+                in_start = x + 1;
+                out_start = mid + 1;
+            }
+
+            if in_start > in_end {
+                return Err(DecryptError::NoMatch(ciphertext as f64));
+            }
+        }
+
+        let coins: [u8; 128] = G::next_coins(&mut self.wide_tape(in_start));
+        if sample_uniform_i128(out_start, out_end, &coins) != ciphertext {
+            return Err(DecryptError::NoMatch(ciphertext as f64));
+        }
+
+        Ok(in_start)
+    }
+
+    fn split_point_exact (&self, in_bounds: (i128, i128), out_bounds: (i128, i128)) -> (i128, i128) {
+        // Middle of out_bounds and split point of in_bounds, as split_point
+        let (out_start, out_end): (i128, i128) = out_bounds;
+        let mid: i128 = out_start - 1 + (out_end - out_start + 2) / 2;
+
+        let mut tape: G::Tape = self.wide_tape(mid);
+        let coins: [u8; 128] = G::next_coins(&mut tape);
+        let mut refill = || G::next_coins(&mut tape);
+
+        (mid, sample_hgd_i128(in_bounds, out_bounds, mid, &coins, Some(&mut refill)))
+    }
+
+    fn split_point (&self, in_range: &ValueRange, out_range: &ValueRange, mid: &f64, cache: Option<&mut TapeCache>) -> ([u8; 128], f64) {
+
+        // Sample the point at which in_range is split, using the tape of mid.
+        // When its first coins come from the cache, the tape is only rebuilt
+        // if sample_hgd needs more coins than those.
+
+        let mut tape: Option<G::Tape> = None;
+        let coins: [u8; 128] = match cache {
+            Some(cache) => cache.coins(mid, || self.tape_gen(mid)),
+            None => G::next_coins(tape.insert(self.tape(mid))),
+        };
+
+        let mut refill = || {
+            let tape: &mut G::Tape = tape.get_or_insert_with(|| {
+                let mut tape: G::Tape = self.tape(mid);
+                G::next_coins(&mut tape);
+                tape
+            });
+            G::next_coins(tape)
+        };
+
+        (coins, sample_hgd(in_range, out_range, mid, &coins, Some(&mut refill)))
+    }
+
+    fn cache_owner (&self) -> [u8; 32] {
+        // Identify the tapes of this OPE without keeping its key in the cache
+        let mut hmac = HmacSha256::new_from_slice(self.encryption_key.as_bytes()).expect("HMAC accepts keys of any size");
+        hmac.update(b"tape cache");
+        hmac.update(&[self.compat as u8]);
+
+        // Generators, or a same generator with other parameters, give other
+        // tapes, and so do other coins for a fixed value
+        hmac.update(&self.tape_gen(&0_f64));
+
+        hmac_sha256_wiped(hmac)
+    }
+
+    fn tape_gen (&self, data: &f64) -> [u8; 128] {
+
+        // Generate a pseudo-random tape of 128 coins for the given value.
+        G::next_coins(&mut self.tape(data))
+    }
+
+    fn wide_tape (&self, data: i128) -> G::Tape {
+        // Same as tape, for integers beyond the exact f64 values
+        match self.compat {
+            OpeCompat::Native => self.generator.tape_from_context(&self.tape_context.0, &wide_tape_input(data)),
+            OpeCompat::Pyope => self.generator.tape_from_context(&self.tape_context.0, &decimal_wide_tape_input(data)),
+        }
+    }
+
+    #[cfg(feature = "fuzzing")]
+    pub fn tape_gen_for_value (&self, v: f64) -> [u8; 128] {
+
+        // Testing only : expose tape_gen so that fuzz targets can feed it any
+        // f64, NaN and infinities included. Not part of the stable API.
+
+        self.tape_gen(&v)
+    }
+
+    fn tape (&self, data: &f64) -> G::Tape {
+
+        // Build the keystream from which the coins of the given value are
+        // taken, seeded with the encoding of the value matching compat.
+
+        match self.compat {
+            OpeCompat::Native => self.generator.tape_from_context(&self.tape_context.0, &tape_input(data)),
+            OpeCompat::Pyope => self.generator.tape_from_context(&self.tape_context.0, &decimal_tape_input(data)),
+        }
+    }
+}
+
+impl<T: OpeDomain> OPE<T> {
+    pub fn from_bounds (encryption_key: impl AsRef<[u8]>, in_start: T, in_end: T, out_start: T::Cipher, out_end: T::Cipher) -> Result<OPE<T>, OpeError> {
+
+        // Build an OPE encrypting integers of type T into integers of the
+        // wider type T::Cipher. The partition is computed on i128 values, so
+        // every bound of T is supported, and ciphertext bounds up to 2^126 in
+        // magnitude. The ranges of config are rounded beyond 2^53.
+
+        let in_bounds: (i128, i128) = (exact_bound(in_start)?, exact_bound(in_end)?);
+        let out_bounds: (i128, i128) = (exact_bound(out_start)?, exact_bound(out_end)?);
+
+        for (start, end) in [in_bounds, out_bounds].iter() {
+            if start > end {
+                return Err(OpeError::InvalidRange(RangeError::StartGreaterThanEnd { start: *start as f64, end: *end as f64 }));
+            }
+        }
+
+        let (in_size, out_size): (i128, i128) = (in_bounds.1 - in_bounds.0 + 1, out_bounds.1 - out_bounds.0 + 1);
+        if in_size > out_size {
+            return Err(OpeError::InRangeTooLarge { in_size: in_size as f64, out_size: out_size as f64 });
+        }
+
+        let encryption_key: EncryptionKey = EncryptionKey::new(encryption_key);
+        let tape_context: TapeContext<HmacSha256> = TapeContext(HmacSha256AesCtr::default().context(encryption_key.as_bytes()));
+
+        Ok(OPE {
+            encryption_key,
+            tape_context,
+            in_range: ValueRange::new(in_bounds.0 as f64, in_bounds.1 as f64),
+            out_range: ValueRange::new(out_bounds.0 as f64, out_bounds.1 as f64),
+            in_bounds,
+            out_bounds,
+            compat: OpeCompat::Native,
+            generator: HmacSha256AesCtr::default(),
+            scale: 1_f64,
+            domain: PhantomData,
+        })
+    }
+}
+
+impl<T: OpeDomain, G: TapeGenerator> OPE<T, G> {
+    pub fn encrypt_value (&self, plaintext: T) -> Result<T::Cipher, EncryptError> {
+
+        // Encrypt the given integer, with integer arithmetic all the way
+
+        match plaintext.to_i128() {
+            Some(value) if self.in_bounds.0 <= value && value <= self.in_bounds.1 => Ok(T::Cipher::from_i128(self.encrypt_exact(value))),
+            _ => Err(EncryptError::OutOfRange { plaintext: plaintext.to_f64(), in_range: self.in_range.clone() }),
+        }
+    }
+
+    pub fn decrypt_value (&self, ciphertext: T::Cipher) -> T {
+
+        // Decrypt the given integer back into a plaintext.
+
+        let result: Result<i128, DecryptError> = match ciphertext.to_i128() {
+            Some(value) if self.out_bounds.0 <= value && value <= self.out_bounds.1 => self.decrypt_exact(value),
+            _ => Err(DecryptError::OutOfRange { ciphertext: ciphertext.to_f64(), out_range: self.out_range.clone() }),
+        };
+
+        result.map(T::from_i128).unwrap_or_else(|error| panic!("{}", error))
+    }
+}
+
+fn check_coin_budget (out_range: &ValueRange) -> Result<(), OpeError> {
+
+    // A ciphertext is sampled from a single tape, an out_range needing more
+    // coins than a tape holds would exhaust it halfway through the sampling.
+
+    let bits: u32 = ceil((out_range.end - out_range.start + 1_f64).log2()) as u32;
+
+    if bits > COIN_BUDGET {
+        return Err(OpeError::RangeTooLarge { bits });
+    }
+
+    Ok(())
+}
+
+fn partition (in_range: &ValueRange, x: f64) -> Result<(Option<ValueRange>, Option<ValueRange>), RangeError> {
+
+    // Plaintexts up to x and beyond x. The hypergeometric sample may send
+    // every plaintext to the same side, leaving the other half empty.
+
+    if x < in_range.start {
+        Ok((None, Some(in_range.clone())))
+    } else if x >= in_range.end {
+        Ok((Some(in_range.clone()), None))
+    } else {
+        in_range.try_split_at(x).map(|(lower, upper)| (Some(lower), Some(upper)))
+    }
+}
+
+fn exact_bound<B: OpeInteger> (bound: B) -> Result<i128, OpeError> {
+    bounded_i128(bound).ok_or_else(|| OpeError::InexactBound(bound.to_f64()))
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use core::cmp::Ordering;
+
+    use super::ConfigError;
+    use super::DecryptError;
+    use super::EncryptError;
+    use super::HmacSha512AesCtr;
+    use super::OPE;
+    use super::OpeBuilder;
+    use super::OpeCompat;
+    use super::OpeConfig;
+    use super::OpeError;
+    use super::RangeError;
+    use super::RoundingMode;
+    use super::TapeCache;
+    use super::TapeGenerator;
+    use super::TapeStep;
+    use super::ValueRange;
+    use super::sample_uniform;
+
+    use crate::testing::lcg_next;
+
+    fn create_ope (key: &str, in_start: f64, in_end: f64, out_start: f64, out_end: f64) -> OPE {
+        OPE::new(key, &ValueRange::new(in_start, in_end), &ValueRange::new(out_start, out_end))
+    }
+
+    #[test]
+    #[should_panic(expected = "OPE : in_range size (101) should not be greater than out_range size (11).")]
+    fn test_new_in_range_too_large () {
+        create_ope("key", 0_f64, 100_f64, 0_f64, 10_f64);
+    }
+
+    #[test]
+    fn test_try_new () {
+        let small_range: ValueRange = ValueRange::new(0_f64, 10_f64);
+        let large_range: ValueRange = ValueRange::new(0_f64, 100_f64);
+
+        assert!(OPE::try_new("key", &small_range, &large_range).is_ok());
+        assert!(OPE::try_new("key", &small_range, &small_range).is_ok());
+
+        let error: OpeError = OPE::try_new("key", &large_range, &small_range).unwrap_err();
+        assert_eq!(error, OpeError::InRangeTooLarge { in_size: 101_f64, out_size: 11_f64 });
+        assert_eq!(format!("{}", error), "OPE : in_range size (101) should not be greater than out_range size (11).");
+    }
+
+    #[test]
+    fn test_builder () {
+        let ope: OPE = OPE::builder().key("key").in_range(0_f64, 100_f64).out_range(0_f64, 1000_f64).build().unwrap();
+        assert_eq!(ope.encrypt(&5_f64), create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64).encrypt(&5_f64));
+
+        // Order of the calls does not matter
+        let ope: OPE = OpeBuilder::new().out_range(0_f64, 1000_f64).in_range(0_f64, 100_f64).key("key").build().unwrap();
+        assert_eq!(ope.encrypt(&5_f64), create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64).encrypt(&5_f64));
+    }
+
+    #[test]
+    fn test_builder_swapped_ranges () {
+        let error: OpeError = OPE::builder().key("key").in_range(0_f64, 1000_f64).out_range(0_f64, 100_f64).build().unwrap_err();
+
+        assert_eq!(error, OpeError::InRangeTooLarge { in_size: 1001_f64, out_size: 101_f64 });
+        assert_eq!(format!("{}", error), "OPE : in_range size (1001) should not be greater than out_range size (101).");
+    }
+
+    #[test]
+    fn test_builder_errors () {
+        assert_eq!(OPE::builder().in_range(0_f64, 10_f64).out_range(0_f64, 100_f64).build().unwrap_err(), OpeError::MissingKey);
+        assert_eq!(OPE::builder().key("key").out_range(0_f64, 100_f64).build().unwrap_err(), OpeError::MissingInRange);
+        assert_eq!(OPE::builder().key("key").in_range(0_f64, 10_f64).build().unwrap_err(), OpeError::MissingOutRange);
+
+        let error: OpeError = OPE::builder().key("key").in_range(10_f64, 0_f64).out_range(0_f64, 100_f64).build().unwrap_err();
+        assert_eq!(error, OpeError::InvalidRange(RangeError::StartGreaterThanEnd { start: 10_f64, end: 0_f64 }));
+        assert_eq!(format!("{}", error), "OPE : invalid range. ValueRange : start value (10) should not be greater than end value (0).");
+    }
+
+    #[test]
+    fn test_builder_strict () {
+        let builder = |out_end: f64, strict: bool| OPE::builder().key("key").in_range(0_f64, 99_f64).out_range(0_f64, out_end).strict(strict).build();
+
+        // Equal sizes and 1.5 times in_range size are only accepted without strict mode
+        assert_eq!(builder(99_f64, true).unwrap_err(), OpeError::InsufficientExpansion { in_size: 100_f64, out_size: 100_f64 });
+        assert_eq!(builder(149_f64, true).unwrap_err(), OpeError::InsufficientExpansion { in_size: 100_f64, out_size: 150_f64 });
+        assert!(builder(99_f64, false).is_ok());
+        assert!(builder(149_f64, false).is_ok());
+
+        assert!(builder(199_f64, true).is_ok());
+        assert!(builder(1_000_f64, true).is_ok());
+
+        let error: OpeError = builder(149_f64, true).unwrap_err();
+        assert_eq!(format!("{}", error), "OPE : out_range size (150) should be at least twice in_range size (100) in strict mode.");
+    }
+
+    #[test]
+    fn test_builder_quantization () {
+        // Amounts up to 10000.00, in cents
+        let ope: OPE = OPE::builder().key("key").in_range(0_f64, 1_000_000_f64).out_range(0_f64, 1_000_000_000_f64).quantization(100_f64).build().unwrap();
+
+        let ciphertext: f64 = ope.encrypt_quantized(&12.34_f64);
+        assert_eq!(ciphertext, ope.encrypt(&1234_f64));
+        assert_eq!(ope.decrypt_quantized(&ciphertext), 12.34_f64);
+        assert_eq!(ope.decrypt(&ciphertext), 1234_f64);
+        assert!(ope.encrypt_quantized(&12.33_f64) < ciphertext);
+
+        // Values off the grid are rounded to its nearest point
+        assert_eq!(ope.encrypt_quantized(&12.3449_f64), ciphertext);
+        assert_eq!(ope.encrypt_quantized(&0.004_f64), ope.encrypt(&0_f64));
+
+        // The scaled value must be in in_range
+        assert_eq!(ope.try_encrypt_quantized(&10_000.01_f64), Err(EncryptError::OutOfRange { plaintext: 1_000_001_f64, in_range: ValueRange::new(0_f64, 1_000_000_f64) }));
+        assert_eq!(ope.try_encrypt_quantized(&-0.01_f64), Err(EncryptError::OutOfRange { plaintext: -1_f64, in_range: ValueRange::new(0_f64, 1_000_000_f64) }));
+        assert!(matches!(ope.try_encrypt_quantized(&f64::NAN), Err(EncryptError::NonFinite(_))));
+
+        // Without quantization, values are only rounded
+        let unit: OPE = OPE::builder().key("key").in_range(0_f64, 1_000_000_f64).out_range(0_f64, 1_000_000_000_f64).build().unwrap();
+        assert_eq!(unit.encrypt_quantized(&1234.4_f64), ciphertext);
+
+        for scale in [0_f64, -100_f64, f64::INFINITY] {
+            assert_eq!(OPE::builder().key("key").in_range(0_f64, 10_f64).out_range(0_f64, 100_f64).quantization(scale).build().unwrap_err(), OpeError::InvalidQuantization(scale));
+        }
+        assert_eq!(format!("{}", OpeError::InvalidQuantization(0_f64)), "OPE : quantization (0) should be a finite number greater than 0.");
+    }
+
+    #[test]
+    fn test_builder_iv () {
+        let builder: OpeBuilder = OPE::builder().key("key").in_range(0_f64, 100_f64).out_range(0_f64, 1000_f64);
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+
+        // A zero IV is the default one
+        let zero_iv: OPE = builder.clone().iv([0; 16]).build().unwrap();
+        assert_eq!(zero_iv.tape_gen(&1_f64), ope.tape_gen(&1_f64));
+        assert!(zero_iv.is_compatible_with(&ope));
+
+        let mut iv: [u8; 16] = [0; 16];
+        iv[15] = 1;
+        let other_iv: OPE = builder.iv(iv).build().unwrap();
+        assert_ne!(other_iv.tape_gen(&1_f64), ope.tape_gen(&1_f64));
+        assert_ne!(other_iv.tape_gen(&1_000_f64), ope.tape_gen(&1_000_f64));
+        assert!(!other_iv.is_compatible_with(&ope));
+        assert_eq!(other_iv.decrypt(&other_iv.encrypt(&42_f64)), 42_f64);
+    }
+
+    #[test]
+    fn test_debug_redacts_key () {
+        let ope: OPE = create_ope("secret key", 0_f64, 100_f64, 0_f64, 1000_f64);
+        let debug: String = format!("{:?}", ope);
+        assert!(!debug.contains("secret key"), "{}", debug);
+        assert!(debug.contains("encryption_key: <redacted>"), "{}", debug);
+
+        let builder: OpeBuilder = OPE::builder().key("secret key");
+        let debug: String = format!("{:?}", builder);
+        assert!(!debug.contains("secret key"), "{}", debug);
+        assert!(debug.contains("encryption_key: Some(<redacted>)"), "{}", debug);
+    }
+
+    #[test]
+    fn test_config () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+        let config: OpeConfig = ope.config();
+
+        assert_eq!(config, OpeConfig { in_range: ValueRange::new(0_f64, 100_f64), out_range: ValueRange::new(0_f64, 1000_f64) });
+
+        let rebuilt: OPE = OPE::from_config(&config, "key").unwrap();
+        assert_eq!(rebuilt.encrypt(&5_f64), ope.encrypt(&5_f64));
+
+        let config: OpeConfig = OpeConfig { in_range: ValueRange::new(0_f64, 1000_f64), out_range: ValueRange::new(0_f64, 100_f64) };
+        assert!(OPE::from_config(&config, "key").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_config_serde () {
+        let ope: OPE = create_ope("key", -100_f64, 100_f64, 0_f64, 1000_f64);
+
+        let json: String = serde_json::to_string(&ope.config()).unwrap();
+        assert_eq!(json, r#"{"in_range":{"start":-100.0,"end":100.0},"out_range":{"start":0.0,"end":1000.0}}"#);
+        assert!(!json.contains("key"));
+
+        let config: OpeConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(config, ope.config());
+        assert_eq!(OPE::from_config(&config, "key").unwrap().encrypt(&5_f64), ope.encrypt(&5_f64));
+
+        // Bounds are validated on deserialization
+        let json: &str = r#"{"in_range":{"start":100.0,"end":-100.0},"out_range":{"start":0.0,"end":1000.0}}"#;
+        assert!(serde_json::from_str::<OpeConfig>(json).is_err());
+    }
+
+    #[test]
+    fn test_config_bytes () {
+        let ope: OPE = create_ope("key", -100_f64, 100_f64, -5_000_f64, 1000_f64);
+        let bytes: Vec<u8> = ope.config().to_bytes();
+
+        assert_eq!(bytes.len(), 36);
+        assert_eq!(bytes[..4].to_vec(), vec![32, 0, 0, 0]);
+        assert_eq!(bytes[4..12].to_vec(), (-100_f64).to_le_bytes().to_vec());
+        assert_eq!(bytes[28..].to_vec(), 1000_f64.to_le_bytes().to_vec());
+
+        let config: OpeConfig = OpeConfig::from_bytes(&bytes).unwrap();
+        assert_eq!(config, ope.config());
+        assert_eq!(OPE::from_config(&config, "key").unwrap().encrypt(&5_f64), ope.encrypt(&5_f64));
+
+        // Truncated or oversized input
+        assert_eq!(OpeConfig::from_bytes(&[]), Err(ConfigError::Truncated { len: 0, expected: 36 }));
+        assert_eq!(OpeConfig::from_bytes(&bytes[..3]), Err(ConfigError::Truncated { len: 3, expected: 36 }));
+        assert_eq!(OpeConfig::from_bytes(&bytes[..20]), Err(ConfigError::Truncated { len: 20, expected: 36 }));
+        let mut longer: Vec<u8> = bytes.clone();
+        longer.push(0);
+        assert_eq!(OpeConfig::from_bytes(&longer), Err(ConfigError::InvalidLength(33)));
+        let mut prefix: Vec<u8> = bytes.clone();
+        prefix[0] = 40;
+        assert_eq!(OpeConfig::from_bytes(&prefix), Err(ConfigError::InvalidLength(40)));
+
+        // Bounds are validated as with serde
+        let reversed: OpeConfig = OpeConfig { in_range: ValueRange::new(0_f64, 10_f64), out_range: ValueRange::new(0_f64, 100_f64) };
+        let mut bytes: Vec<u8> = reversed.to_bytes();
+        bytes[4..12].copy_from_slice(&20_f64.to_le_bytes());
+        assert_eq!(OpeConfig::from_bytes(&bytes), Err(ConfigError::InvalidRange(RangeError::StartGreaterThanEnd { start: 20_f64, end: 10_f64 })));
+        bytes[4..12].copy_from_slice(&0.5_f64.to_le_bytes());
+        assert_eq!(OpeConfig::from_bytes(&bytes), Err(ConfigError::InvalidRange(RangeError::NonIntegerStart(0.5_f64))));
+        bytes[28..].copy_from_slice(&f64::INFINITY.to_le_bytes());
+        assert!(matches!(OpeConfig::from_bytes(&bytes), Err(ConfigError::InvalidRange(_))));
+    }
+
+    #[test]
+    fn test_config_fingerprint () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+        let fingerprint: [u8; 32] = ope.config_fingerprint();
+
+        assert_eq!(create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64).config_fingerprint(), fingerprint);
+        assert_eq!(OPE::from_config(&ope.config(), "key").unwrap().config_fingerprint(), fingerprint);
+
+        let others: Vec<OPE> = vec![
+            create_ope("another key", 0_f64, 100_f64, 0_f64, 1000_f64),
+            create_ope("key", 0_f64, 99_f64, 0_f64, 1000_f64),
+            create_ope("key", -1_f64, 100_f64, 0_f64, 1000_f64),
+            create_ope("key", 0_f64, 100_f64, 0_f64, 1001_f64),
+            create_ope("key", 0_f64, 100_f64, 1_f64, 1000_f64),
+            OPE::builder().key("key").in_range(0_f64, 100_f64).out_range(0_f64, 1000_f64).compat(OpeCompat::Pyope).build().unwrap(),
+        ];
+        for other in others.iter() {
+            assert!(!other.is_compatible_with(&ope));
+            assert_ne!(other.config_fingerprint(), fingerprint);
+        }
+
+        let other_generator: OPE<f64, HmacSha512AesCtr> = OPE::with_generator("key", &ValueRange::new(0_f64, 100_f64), &ValueRange::new(0_f64, 1000_f64), HmacSha512AesCtr).unwrap();
+        assert_ne!(other_generator.config_fingerprint(), fingerprint);
+
+        // The key cannot be read from it, nor is it the tape cache identifier
+        assert!(!fingerprint.windows(3).any(|window| window == b"key"));
+        assert_ne!(fingerprint, ope.cache_owner());
+    }
+
+    #[test]
+    fn test_is_compatible_with () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+
+        assert!(ope.is_compatible_with(&ope));
+        assert!(ope.is_compatible_with(&create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64)));
+        assert!(ope.is_compatible_with(&OPE::from_config(&ope.config(), "key").unwrap()));
+
+        assert!(!ope.is_compatible_with(&create_ope("other key", 0_f64, 100_f64, 0_f64, 1000_f64)));
+        assert!(!ope.is_compatible_with(&create_ope("key", 0_f64, 101_f64, 0_f64, 1000_f64)));
+        assert!(!ope.is_compatible_with(&create_ope("key", 0_f64, 100_f64, 0_f64, 1001_f64)));
+
+        let pyope: OPE = OPE::builder().key("key").in_range(0_f64, 100_f64).out_range(0_f64, 1000_f64).compat(OpeCompat::Pyope).build().unwrap();
+        assert!(!ope.is_compatible_with(&pyope));
+    }
+
+    #[test]
+    fn test_max_depth () {
+        assert_eq!(create_ope("key", 0_f64, 5_f64, 0_f64, 15_f64).max_depth(), 3);
+        assert_eq!(create_ope("key", 0_f64, 15_f64, 0_f64, 15_f64).max_depth(), 4);
+        assert_eq!(create_ope("key", -1000_f64, 999_f64, 0_f64, 1_000_000_f64).max_depth(), 11);
+        assert_eq!(create_ope("key", 7_f64, 7_f64, 0_f64, 10_f64).max_depth(), 0);
+    }
+
+    #[test]
+    fn test_out_range_bits () {
+        assert_eq!(create_ope("key", 0_f64, 5_f64, 0_f64, 15_f64).out_range_bits(), 4_f64);
+        assert_eq!(create_ope("key", 0_f64, 15_f64, 0_f64, 15_f64).out_range_bits(), 4_f64);
+        assert_eq!(create_ope("key", 0_f64, 100_f64, 1_f64, 1024_f64).out_range_bits(), 10_f64);
+
+        let bits: f64 = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64).out_range_bits();
+        assert!((bits - 1001_f64.log2()).abs() < 1e-12);
+    }
+
+    #[test]
+    #[cfg(not(feature = "decimal-tape"))]
+    fn test_tape_gen () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+
+        let expected_result: [u8; 128] = [
+            1, 0, 1, 0, 1, 1, 1, 1, 1, 1, 0, 1, 1, 0, 1, 1, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 0,
+            1, 1, 0, 0, 1, 0, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 0, 1, 0, 0, 0, 0, 1, 1, 0, 0, 1,
+            1, 0, 1, 1, 1, 1, 1, 0, 1, 1, 0, 0, 1, 1, 1, 0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 0, 1, 0, 1, 1, 0, 0,
+            0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 1, 0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 1, 1, 1, 0, 1, 0, 0, 0
+        ];
+        assert_eq!(ope.tape_gen(&1_f64).to_vec(), expected_result.to_vec());
+
+        let expected_result: [u8; 128] = [
+            0, 0, 0, 0, 1, 0, 0, 1, 1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 0, 0, 1, 1, 1, 0, 1, 1, 0, 0, 1, 0, 0,
+            0, 0, 0, 1, 0, 1, 1, 0, 0, 1, 1, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 0, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1,
+            0, 1, 0, 1, 1, 0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 0, 1, 0, 1, 1, 0,
+            0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 1, 0, 1, 0, 1, 1, 1, 0, 1, 1, 0, 0, 1, 1, 1, 1, 1, 1, 0, 1, 1
+        ];
+        assert_eq!(ope.tape_gen(&1_000_f64).to_vec(), expected_result.to_vec());
+    }
+
+    #[test]
+    #[cfg(feature = "decimal-tape")]
+    fn test_tape_gen () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+
+        let expected_result: [u8; 128] = [
+            1, 0, 1, 1, 1, 1, 1, 0, 0, 1, 1, 1, 1, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 0, 1, 0, 1, 0, 1, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 1, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 1, 1, 0, 1, 0, 0, 0, 0, 1,
+            1, 1, 0, 0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 0, 1, 1, 1, 1,
+            1, 0, 0, 0, 1, 1, 0, 0, 1, 1, 1, 0, 1, 1, 0, 1, 1, 0, 0, 0, 0, 1, 0, 0, 1, 1, 1, 0, 1, 1, 0, 0
+        ];
+        assert_eq!(ope.tape_gen(&1_f64).to_vec(), expected_result.to_vec());
+
+        let expected_result: [u8; 128] = [
+            0, 1, 0, 0, 1, 0, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1, 1, 0, 0, 0, 1, 1, 0, 0, 1, 0, 1, 0, 0, 1, 1, 0,
+            0, 1, 1, 1, 1, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 0, 1, 0, 1, 0, 0, 0, 1, 0, 1, 0, 0, 1,
+            1, 0, 0, 0, 1, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 0, 0, 1, 1, 1, 1, 1, 0, 0, 0,
+            1, 0, 0, 0, 0, 1, 1, 1, 0, 0, 1, 0, 1, 0, 1, 1, 0, 1, 0, 0, 1, 0, 0, 0, 1, 1, 1, 1, 0, 0, 1, 0
+        ];
+        assert_eq!(ope.tape_gen(&1_000_f64).to_vec(), expected_result.to_vec());
+    }
+
+    #[test]
+    fn test_encrypt () {
+        // Expected ciphertexts depend on the encoding of the values fed to tape_gen
+        let expected: [f64; 5] = if cfg!(feature = "decimal-tape") {
+            [0_f64, 3_f64, 11_f64, 4_f64, 96_f64]
+        } else {
+            [0_f64, 1_f64, 15_f64, 8_f64, 35_f64]
+        };
+
+        let ope: OPE = create_ope("key", 0_f64, 5_f64, 0_f64, 15_f64);
+        assert_eq!(ope.encrypt(&0_f64), expected[0]);
+        assert_eq!(ope.encrypt(&1_f64), expected[1]);
+        assert_eq!(ope.encrypt(&5_f64), expected[2]);
+
+        let ope: OPE = create_ope("another key", 0_f64, 10_f64, 0_f64, 20_f64);
+        assert_eq!(ope.encrypt(&3_f64), expected[3]);
+
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+        assert_eq!(ope.encrypt(&5_f64), expected[4]);
+    }
+
+    #[test]
+    fn test_encrypt_pyope () {
+        // Reference ciphertexts computed in Python following pyope's ope, stat
+        // and hgd modules, for pyope's default ranges and a negative range
+        let ope: OPE = OPE::builder().key("k").in_range(0_f64, 32767_f64).out_range(0_f64, 2147483647_f64).compat(OpeCompat::Pyope).build().unwrap();
+        let ciphertexts: Vec<f64> = [0_f64, 1_f64, 1000_f64, 12345_f64, 32767_f64].iter().map(|plaintext| ope.encrypt(plaintext)).collect();
+        assert_eq!(ciphertexts, vec![1506_f64, 53118_f64, 62177102_f64, 802057743_f64, 2147388551_f64]);
+        assert_eq!(ope.decrypt(&802057743_f64), 12345_f64);
+
+        let ope: OPE = OPE::builder().key("pyope").in_range(-1000_f64, 1000_f64).out_range(-100000_f64, 100000_f64).compat(OpeCompat::Pyope).build().unwrap();
+        let ciphertexts: Vec<f64> = [-1000_f64, -3_f64, 0_f64, 500_f64, 1000_f64].iter().map(|plaintext| ope.encrypt(plaintext)).collect();
+        assert_eq!(ciphertexts, vec![-99899_f64, 244_f64, 388_f64, 48715_f64, 99797_f64]);
+
+        // The native encoding differs, unless it is decimal as well
+        let native: OPE = create_ope("pyope", -1000_f64, 1000_f64, -100000_f64, 100000_f64);
+        assert_eq!(native.encrypt(&500_f64) == 48715_f64, cfg!(feature = "decimal-tape"));
+    }
+
+    #[test]
+    fn test_derive () {
+        let in_range: ValueRange = ValueRange::new(0_f64, 1000_f64);
+        let out_range: ValueRange = ValueRange::new(0_f64, 1_000_000_f64);
+
+        let age: OPE = OPE::derive(b"master key", "users.age", &in_range, &out_range).unwrap();
+        let salary: OPE = OPE::derive(b"master key", "users.salary", &in_range, &out_range).unwrap();
+        let master: OPE = OPE::new(b"master key", &in_range, &out_range);
+
+        assert_ne!(age.encrypt(&42_f64), salary.encrypt(&42_f64));
+        assert_ne!(age.encrypt(&42_f64), master.encrypt(&42_f64));
+        assert_eq!(age.encrypt(&42_f64), OPE::derive(b"master key", "users.age", &in_range, &out_range).unwrap().encrypt(&42_f64));
+        assert_eq!(salary.decrypt(&salary.encrypt(&42_f64)), 42_f64);
+
+        let error: OpeError = OPE::derive(b"master key", "users.age", &out_range, &in_range).unwrap_err();
+        assert_eq!(error, OpeError::InRangeTooLarge { in_size: 1_000_001_f64, out_size: 1001_f64 });
+    }
+
+    #[test]
+    fn test_encrypt_byte_key () {
+        // Any bytes can be used as a key, String, &str and Vec<u8> holding the
+        // same bytes giving the same scheme
+        let key: Vec<u8> = vec![0xff_u8, 0x00_u8, 0xfe_u8, 0x80_u8];
+        assert!(String::from_utf8(key.clone()).is_err());
+
+        let ope: OPE = OPE::builder().key(&key).in_range(0_f64, 1000_f64).out_range(0_f64, 100000_f64).compat(OpeCompat::Pyope).build().unwrap();
+        let ciphertexts: Vec<f64> = [0_f64, 1_f64, 500_f64, 999_f64, 1000_f64].iter().map(|plaintext| ope.encrypt(plaintext)).collect();
+        assert_eq!(ciphertexts, vec![9_f64, 58_f64, 48956_f64, 99896_f64, 99984_f64]);
+        assert_eq!(ope.decrypt(&48956_f64), 500_f64);
+
+        let bytes: Vec<u8> = b"key".to_vec();
+        let from_vec: OPE = OPE::new(bytes, &ValueRange::new(0_f64, 100_f64), &ValueRange::new(0_f64, 1000_f64));
+        let from_string: OPE = OPE::new(String::from("key"), &ValueRange::new(0_f64, 100_f64), &ValueRange::new(0_f64, 1000_f64));
+        assert_eq!(from_vec.encrypt(&42_f64), from_string.encrypt(&42_f64));
+        assert_eq!(from_vec.encrypt(&42_f64), create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64).encrypt(&42_f64));
+    }
+
+    #[test]
+    fn test_encrypt_large_out_range () {
+        // out_range spans 2^45 values for 11 plaintexts, so that the final
+        // uniform sample consumes more than 32 coins, as pyope does
+        let ope: OPE = OPE::builder().key("k").in_range(0_f64, 10_f64).out_range(0_f64, 2_f64.powi(45) - 1_f64).compat(OpeCompat::Pyope).build().unwrap();
+        let ciphertexts: Vec<f64> = [0_f64, 1_f64, 5_f64, 9_f64, 10_f64].iter().map(|plaintext| ope.encrypt(plaintext)).collect();
+        assert_eq!(ciphertexts, vec![101069973593_f64, 3564741258922_f64, 22608107273003_f64, 32760230703929_f64, 33943912792263_f64]);
+
+        for (plaintext, ciphertext) in [0_f64, 1_f64, 5_f64, 9_f64, 10_f64].iter().zip(ciphertexts.iter()) {
+            assert_eq!(ope.decrypt(ciphertext), *plaintext);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "should be in in_range")]
+    fn test_encrypt_out_of_range () {
+        let ope: OPE = create_ope("key", 0_f64, 5_f64, 0_f64, 15_f64);
+        ope.encrypt(&6_f64);
+    }
+
+    #[test]
+    fn test_try_encrypt () {
+        let ope: OPE = create_ope("key", 0_f64, 5_f64, 0_f64, 15_f64);
+        assert_eq!(ope.try_encrypt(&0_f64), Ok(ope.encrypt(&0_f64)));
+        assert_eq!(ope.try_encrypt(&5_f64), Ok(ope.encrypt(&5_f64)));
+
+        let error: EncryptError = ope.try_encrypt(&-1_f64).unwrap_err();
+        assert_eq!(error, EncryptError::OutOfRange { plaintext: -1_f64, in_range: ValueRange::new(0_f64, 5_f64) });
+        assert_eq!(format!("{}", error), "OPE : plaintext (-1) should be in in_range ValueRange { start: 0.0, end: 5.0 }.");
+
+        let error: EncryptError = ope.try_encrypt(&6_f64).unwrap_err();
+        assert_eq!(error, EncryptError::OutOfRange { plaintext: 6_f64, in_range: ValueRange::new(0_f64, 5_f64) });
+    }
+
+    #[test]
+    fn test_try_encrypt_non_finite () {
+        let ope: OPE = create_ope("key", 0_f64, 5_f64, 0_f64, 15_f64);
+
+        let error: EncryptError = ope.try_encrypt(&f64::INFINITY).unwrap_err();
+        assert_eq!(error, EncryptError::NonFinite(f64::INFINITY));
+        assert_eq!(format!("{}", error), "OPE : plaintext should be a finite f64 number. Found inf");
+        assert_eq!(ope.try_encrypt(&f64::NEG_INFINITY), Err(EncryptError::NonFinite(f64::NEG_INFINITY)));
+        assert!(matches!(ope.try_encrypt(&f64::NAN), Err(EncryptError::NonFinite(plaintext)) if plaintext.is_nan()));
+        assert!(matches!(ope.encrypt_batch(&[1_f64, f64::NAN]), Err(EncryptError::NonFinite(plaintext)) if plaintext.is_nan()));
+
+        // Nor are fractional plaintexts, which would share the tape of their
+        // integer part
+        let error: EncryptError = ope.try_encrypt(&2.5_f64).unwrap_err();
+        assert_eq!(error, EncryptError::NotInteger(2.5_f64));
+        assert_eq!(format!("{}", error), "OPE : plaintext should be an integer. Found 2.5");
+        assert_eq!(ope.encrypt_batch(&[1_f64, 4.25_f64]), Err(EncryptError::NotInteger(4.25_f64)));
+
+        // Non finite bounds are rejected when building the OPE
+        let error: OpeError = OPE::builder().key("key").in_range(0_f64, f64::INFINITY).out_range(0_f64, 15_f64).build().unwrap_err();
+        assert_eq!(error, OpeError::InvalidRange(RangeError::NonFinite(f64::INFINITY)));
+        assert!(matches!(OPE::builder().key("key").in_range(0_f64, 5_f64).out_range(f64::NAN, 15_f64).build(), Err(OpeError::InvalidRange(RangeError::NonFinite(bound))) if bound.is_nan()));
+    }
+
+    #[test]
+    #[should_panic(expected = "OPE : plaintext should be a finite f64 number. Found NaN")]
+    fn test_encrypt_nan () {
+        let ope: OPE = create_ope("key", 0_f64, 5_f64, 0_f64, 15_f64);
+        ope.encrypt(&f64::NAN);
+    }
+
+    #[test]
+    fn test_encrypt_rounded () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+
+        let nearest: RoundingMode = RoundingMode::default();
+
+        assert_eq!(ope.try_encrypt(&5.5_f64), Err(EncryptError::NotInteger(5.5_f64)));
+        assert_eq!(ope.encrypt_rounded(&5.5_f64, nearest), ope.encrypt(&6_f64));
+        assert_eq!(ope.encrypt_rounded(&5.4_f64, nearest), ope.encrypt(&5_f64));
+        assert_eq!(ope.encrypt_rounded(&5_f64, nearest), ope.encrypt(&5_f64));
+        assert_eq!(ope.decrypt(&ope.encrypt_rounded(&99.7_f64, nearest)), 100_f64);
+
+        assert_eq!(ope.try_encrypt_rounded(&-0.4_f64, nearest), Ok(ope.encrypt(&0_f64)));
+        assert!(matches!(ope.try_encrypt_rounded(&100.5_f64, nearest), Err(EncryptError::OutOfRange { plaintext, .. }) if plaintext == 101_f64));
+        assert!(matches!(ope.try_encrypt_rounded(&f64::NAN, nearest), Err(EncryptError::NonFinite(_))));
+    }
+
+    #[test]
+    fn test_rounding_mode () {
+        let ope: OPE = create_ope("key", -10_f64, 10_f64, 0_f64, 1000_f64);
+
+        let expected: Vec<(RoundingMode, f64, f64, f64)> = vec![
+            // Mode, then the integers of 2.5, -2.5 and 3.5
+            (RoundingMode::Nearest, 3_f64, -2_f64, 4_f64),
+            (RoundingMode::NearestEven, 2_f64, -2_f64, 4_f64),
+            (RoundingMode::Floor, 2_f64, -3_f64, 3_f64),
+            (RoundingMode::Ceil, 3_f64, -2_f64, 4_f64),
+            (RoundingMode::Truncate, 2_f64, -2_f64, 3_f64),
+        ];
+
+        for (mode, positive, negative, odd) in expected.iter() {
+            assert_eq!(mode.round(2.5_f64), *positive, "{:?}", mode);
+            assert_eq!(mode.round(-2.5_f64), *negative, "{:?}", mode);
+            assert_eq!(mode.round(3.5_f64), *odd, "{:?}", mode);
+            assert_eq!(mode.round(7_f64), 7_f64, "{:?}", mode);
+            assert_eq!(ope.encrypt_rounded(&2.5_f64, *mode), ope.encrypt(positive));
+            assert_eq!(ope.encrypt_rounded(&-2.5_f64, *mode), ope.encrypt(negative));
+        }
+
+        assert_eq!(RoundingMode::Truncate.round(-2.7_f64), -2_f64);
+        assert_eq!(RoundingMode::Floor.round(-2.2_f64), -3_f64);
+    }
+
+    #[test]
+    fn test_encrypt_saturating () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+
+        // Inside in_range, same as encrypt
+        for plaintext in [0_f64, 1_f64, 42_f64, 100_f64].iter() {
+            assert_eq!(ope.encrypt_saturating(plaintext), ope.encrypt(plaintext));
+        }
+
+        // Beyond it, same as the nearest bound
+        for plaintext in [101_f64, 1e12_f64, f64::INFINITY].iter() {
+            assert_eq!(ope.encrypt_saturating(plaintext), ope.encrypt(&100_f64));
+        }
+        for plaintext in [-1_f64, -1e12_f64, f64::NEG_INFINITY].iter() {
+            assert_eq!(ope.encrypt_saturating(plaintext), ope.encrypt(&0_f64));
+        }
+        assert_eq!(ope.decrypt(&ope.encrypt_saturating(&5_000_f64)), 100_f64);
+
+        // Fractional values, rounded before being clamped
+        assert_eq!(ope.encrypt_saturating(&150.5_f64), ope.encrypt(&100_f64));
+        assert_eq!(ope.encrypt_saturating(&-3.7_f64), ope.encrypt(&0_f64));
+        assert_eq!(ope.encrypt_saturating(&5.5_f64), ope.encrypt(&6_f64));
+        assert_eq!(ope.encrypt_saturating(&99.6_f64), ope.encrypt(&100_f64));
+    }
+
+    #[test]
+    #[should_panic(expected = "OPE : plaintext should be a finite f64 number. Found NaN")]
+    fn test_encrypt_saturating_nan () {
+        create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64).encrypt_saturating(&f64::NAN);
+    }
+
+    #[test]
+    fn test_suggest_out_range () {
+        let in_range: ValueRange = ValueRange::new(-50_f64, 49_f64);
+
+        for expansion_bits in [0_u32, 1_u32, 8_u32, 20_u32, 46_u32].iter() {
+            let out_range: ValueRange = OPE::suggest_out_range(&in_range, *expansion_bits);
+            assert_eq!(out_range.start, 0_f64);
+            assert_eq!(out_range.size(), 100_f64 * 2_f64.powi(*expansion_bits as i32));
+            assert!(out_range.size() >= in_range.size());
+            assert!(OPE::try_new("key", &in_range, &out_range).is_ok());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "OPE : out_range size should not be greater than 2^53, 47 expansion bits are too many")]
+    fn test_suggest_out_range_too_large () {
+        OPE::suggest_out_range(&ValueRange::new(-50_f64, 49_f64), 47);
+    }
+
+    #[test]
+    fn test_encrypt_u128 () {
+        // Same ciphertexts as encrypt when out_range is small enough for both
+        let ope: OPE = create_ope("key", 0_f64, 1000_f64, 0_f64, 1_000_000_f64);
+        for plaintext in [0_u64, 1_u64, 500_u64, 999_u64, 1000_u64].iter() {
+            assert_eq!(ope.encrypt_u128(*plaintext), ope.encrypt(&(*plaintext as f64)) as u128);
+        }
+
+        // Exact ciphertexts, which f64 values could not hold, beyond 2^53
+        let ope: OPE = OPE::new_wide("key", &ValueRange::new(0_f64, 1000_f64), &ValueRange::new(0_f64, 2_f64.powi(120)));
+        let ciphertexts: Vec<u128> = (0..=1000).step_by(50).map(|plaintext| ope.encrypt_u128(plaintext)).collect();
+
+        assert!(ciphertexts.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(ciphertexts.iter().all(|ciphertext| *ciphertext <= 1_u128 << 120));
+        assert!(ciphertexts.iter().any(|ciphertext| (*ciphertext as f64) as u128 != *ciphertext));
+        assert_eq!(ciphertexts, (0..=1000).step_by(50).map(|plaintext| ope.encrypt_u128(plaintext)).collect::<Vec<u128>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "OPE : plaintext (1001) should be in in_range")]
+    fn test_encrypt_u128_out_of_range () {
+        OPE::new_wide("key", &ValueRange::new(0_f64, 1000_f64), &ValueRange::new(0_f64, 2_f64.powi(120))).encrypt_u128(1001);
+    }
+
+    #[test]
+    fn test_try_new_wide () {
+        let in_range: ValueRange = ValueRange::new(0_f64, 10_f64);
+        let out_range: ValueRange = ValueRange::new(0_f64, 2_f64.powi(60));
+
+        // f64 ciphertexts cannot be exact in such an out_range
+        let error: OpeError = OPE::try_new("key", &in_range, &out_range).unwrap_err();
+        assert_eq!(error, OpeError::OutRangeTooLarge(out_range.clone()));
+        assert_eq!(format!("{}", error), "OPE : out_range ValueRange { start: 0.0, end: 1.152921504606847e18 } holds more than 2^53 values, which f64 ciphertexts cannot represent exactly. Use OPE::try_new_wide and encrypt_u128 for such a range.");
+        assert!(OPE::builder().key("key").in_range(0_f64, 10_f64).out_range(0_f64, 2_f64.powi(60)).build().is_err());
+
+        // Nor can plaintexts be encrypted into it with anything but encrypt_u128
+        let ope: OPE = OPE::try_new_wide("key", &in_range, &out_range).unwrap();
+        assert_eq!(ope.try_encrypt(&5_f64), Err(EncryptError::WideOutRange(out_range.clone())));
+        assert_eq!(ope.encrypt_batch(&[5_f64]), Err(EncryptError::WideOutRange(out_range.clone())));
+        assert!(ope.encrypt_u128(5) <= 1_u128 << 60);
+
+        // Smaller ranges give the same OPE as try_new
+        let small: ValueRange = ValueRange::new(0_f64, 1000_f64);
+        assert_eq!(OPE::new_wide("key", &in_range, &small).encrypt(&5_f64), OPE::new("key", &in_range, &small).encrypt(&5_f64));
+
+        assert_eq!(OPE::try_new_wide("key", &in_range, &ValueRange::new(-1_f64, 2_f64.powi(60))).unwrap_err(), OpeError::InvalidWideRange(ValueRange::new(-1_f64, 2_f64.powi(60))));
+        assert!(OPE::try_new_wide("key", &in_range, &ValueRange::new(0_f64, 2_f64.powi(127))).is_err());
+        assert!(matches!(OPE::try_new_wide("key", &ValueRange::new(0_f64, 2_f64.powi(60)), &ValueRange::new(0_f64, 2_f64.powi(100))), Err(OpeError::InRangeTooLarge { .. })));
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_ephemeral () {
+        let in_range: ValueRange = ValueRange::new(0_f64, 1000_f64);
+        let out_range: ValueRange = ValueRange::new(0_f64, 1_000_000_000_f64);
+
+        let ope: OPE = OPE::ephemeral(&in_range, &out_range);
+        let other: OPE = OPE::ephemeral(&in_range, &out_range);
+        assert_ne!(ope.encrypt(&500_f64), other.encrypt(&500_f64));
+        assert_eq!(ope.decrypt(&ope.encrypt(&500_f64)), 500_f64);
+        assert!(ope.encrypt(&499_f64) < ope.encrypt(&500_f64));
+    }
+
+    #[test]
+    fn test_leakage_estimate () {
+        let ope: OPE = create_ope("key", 0_f64, 255_f64, 0_f64, 1_000_000_f64);
+
+        let uniform: Vec<(f64, u64)> = ope.config().in_range.iter().map(|plaintext| (plaintext, 10_u64)).collect();
+        let skewed: Vec<(f64, u64)> = ope.config().in_range.iter().map(|plaintext| (plaintext, if plaintext < 4_f64 { 1000_u64 } else { 1_u64 })).collect();
+
+        let uniform_score: f64 = ope.leakage_estimate(&uniform).unwrap();
+        let skewed_score: f64 = ope.leakage_estimate(&skewed).unwrap();
+        assert!(uniform_score.abs() < 1e-12, "uniform score {}", uniform_score);
+        assert!(skewed_score > uniform_score + 0.3_f64, "skewed score {} against {}", skewed_score, uniform_score);
+        assert!(skewed_score < 1_f64);
+
+        // A single plaintext gives everything away, entries adding up
+        assert_eq!(ope.leakage_estimate(&[(7_f64, 5_u64)]), Ok(1_f64));
+        assert_eq!(ope.leakage_estimate(&[(7_f64, 5_u64), (7_f64, 3_u64), (8_f64, 0_u64)]), Ok(1_f64));
+
+        // Half of in_range, uniformly, loses a bit out of 8
+        let half: Vec<(f64, u64)> = (0..128).map(|plaintext| (plaintext as f64, 1_u64)).collect();
+        assert!((ope.leakage_estimate(&half).unwrap() - 0.125_f64).abs() < 1e-12);
+
+        // Entries which could not be encrypted
+        assert!(matches!(ope.leakage_estimate(&[(7_f64, 5_u64), (256_f64, 1_u64)]), Err(EncryptError::OutOfRange { .. })));
+        assert_eq!(ope.leakage_estimate(&[(7.5_f64, 5_u64)]), Err(EncryptError::NotInteger(7.5_f64)));
+        assert!(matches!(ope.leakage_estimate(&[(f64::NAN, 5_u64)]), Err(EncryptError::NonFinite(_))));
+    }
+
+    #[test]
+    fn test_encrypt_single_value () {
+        let ope: OPE = create_ope("key", 42_f64, 42_f64, 0_f64, 1000_f64);
+
+        let ciphertext: f64 = ope.encrypt(&42_f64);
+        assert!(ope.config().out_range.contains(&ciphertext));
+        assert_eq!(ope.encrypt(&42_f64), ciphertext);
+        assert_eq!(create_ope("key", 42_f64, 42_f64, 0_f64, 1000_f64).encrypt(&42_f64), ciphertext);
+        assert_eq!(ope.decrypt(&ciphertext), 42_f64);
+
+        // Nothing is split, and the ciphertext is the uniform sample of the
+        // coins of the plaintext
+        let (traced, steps): (f64, Vec<TapeStep>) = ope.encrypt_traced(&42_f64);
+        assert_eq!(traced, ciphertext);
+        assert!(steps.is_empty());
+        assert_eq!(ciphertext, sample_uniform(&ope.config().out_range, &ope.tape_gen(&42_f64)));
+
+        assert_eq!(ope.try_encrypt(&41_f64), Err(EncryptError::OutOfRange { plaintext: 41_f64, in_range: ValueRange::new(42_f64, 42_f64) }));
+    }
+
+    #[test]
+    fn test_encrypt_equal_sizes () {
+        let ope: OPE = create_ope("key", -10_f64, 20_f64, 100_f64, 130_f64);
+
+        for plaintext in ope.config().in_range.iter() {
+            let ciphertext: f64 = ope.encrypt(&plaintext);
+            assert_eq!(ciphertext, plaintext + 110_f64);
+            assert_eq!(ope.encrypt_recursive(&plaintext, &ope.in_range, &ope.out_range, None, None), Ok(ciphertext));
+            assert_eq!(ope.decrypt(&ciphertext), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_from_parts_unchecked () {
+        let config: OpeConfig = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64).config();
+        let ope: OPE = OPE::from_parts_unchecked("key", &config.in_range, &config.out_range);
+
+        assert_eq!(ope.config(), config);
+        for plaintext in [0_f64, 5_f64, 100_f64] {
+            assert_eq!(ope.encrypt(&plaintext), create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64).encrypt(&plaintext));
+        }
+        assert!(ope.is_compatible_with(&OPE::new("key", &config.in_range, &config.out_range)));
+
+        let wide: ValueRange = ValueRange::new(0_f64, 2_f64.powi(60));
+        assert_eq!(OPE::from_parts_unchecked("key", &config.in_range, &wide).encrypt_u128(5), OPE::new_wide("key", &config.in_range, &wide).encrypt_u128(5));
+    }
+
+    #[test]
+    fn test_try_new_coin_budget () {
+        let in_range: ValueRange = ValueRange::new(0_f64, 10_f64);
+        let out_range: ValueRange = ValueRange::new(0_f64, 2_f64.powi(130));
+
+        // Sampling a ciphertext of such an out_range would exhaust its tape
+        let error: OpeError = OPE::try_new("key", &in_range, &out_range).unwrap_err();
+        assert_eq!(error, OpeError::RangeTooLarge { bits: 130 });
+        assert_eq!(format!("{}", error), "OPE : out_range needs 130 bits of coins to sample a ciphertext, more than the 128 coins of a tape.");
+        assert_eq!(OPE::try_new_wide("key", &in_range, &out_range).unwrap_err(), OpeError::RangeTooLarge { bits: 130 });
+        assert_eq!(OPE::builder().key("key").in_range(0_f64, 10_f64).out_range(0_f64, 2_f64.powi(200)).build().unwrap_err(), OpeError::RangeTooLarge { bits: 200 });
+
+        // 128 coins are enough up to 2^128 values
+        assert_eq!(OPE::try_new("key", &in_range, &ValueRange::new(0_f64, 2_f64.powi(127))).unwrap_err(), OpeError::OutRangeTooLarge(ValueRange::new(0_f64, 2_f64.powi(127))));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_safe_encrypt () {
+        let ope: OPE = create_ope("key", -100_f64, 100_f64, -1000_f64, 1000_f64);
+
+        for plaintext in [-100_f64, -0_f64, 0_f64, 42_f64, 100_f64] {
+            assert_eq!(ope.safe_encrypt(&plaintext), Ok(ope.encrypt(&plaintext)));
+        }
+
+        assert!(matches!(ope.safe_encrypt(&f64::NAN), Err(EncryptError::NonFinite(_))));
+        for plaintext in [f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(ope.safe_encrypt(&plaintext), Err(EncryptError::NonFinite(plaintext)));
+        }
+        for plaintext in [0.5_f64, -99.5_f64, 4.9e-324_f64, -f64::MIN_POSITIVE, 1e-300_f64] {
+            assert_eq!(ope.safe_encrypt(&plaintext), Err(EncryptError::NotInteger(plaintext)));
+        }
+        for plaintext in [101_f64, -101_f64, f64::MAX, f64::MIN, 2_f64.powi(53), 1e300_f64] {
+            assert!(matches!(ope.safe_encrypt(&plaintext), Err(EncryptError::OutOfRange { .. })));
+        }
+
+        let wide: OPE = OPE::try_new_wide("key", &ValueRange::new(0_f64, 100_f64), &ValueRange::new(0_f64, 2_f64.powi(60))).unwrap();
+        assert!(matches!(wide.safe_encrypt(&5_f64), Err(EncryptError::WideOutRange(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_safe_encrypt_catches_panics () {
+        struct PanickingGenerator;
+
+        impl TapeGenerator for PanickingGenerator {
+            type Tape = ();
+            type Context = ();
+
+            fn context (&self, _key: &[u8]) {}
+
+            fn tape_from_context (&self, _context: &(), _input: &[u8]) {
+                panic!("no tape");
+            }
+
+            fn next_coins (_tape: &mut ()) -> [u8; 128] {
+                [0; 128]
+            }
+        }
+
+        let ope: OPE<f64, PanickingGenerator> = OPE::with_generator("key", &ValueRange::new(0_f64, 100_f64), &ValueRange::new(0_f64, 1000_f64), PanickingGenerator).unwrap();
+        assert_eq!(ope.safe_encrypt(&5_f64), Err(EncryptError::Panicked));
+        assert_eq!(ope.safe_encrypt(&101_f64), Err(EncryptError::OutOfRange { plaintext: 101_f64, in_range: ValueRange::new(0_f64, 100_f64) }));
+    }
+
+    #[test]
+    fn test_encrypt_batch () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+
+        let plaintexts: Vec<f64> = vec![5_f64, 0_f64, 100_f64, 5_f64];
+        let expected: Vec<f64> = plaintexts.iter().map(|plaintext| ope.encrypt(plaintext)).collect();
+        assert_eq!(ope.encrypt_batch(&plaintexts), Ok(expected));
+
+        assert_eq!(ope.encrypt_batch(&[]), Ok(vec![]));
+
+        // The first invalid plaintext is reported, whatever its position
+        let error: EncryptError = ope.encrypt_batch(&[5_f64, 101_f64, 0_f64, -1_f64]).unwrap_err();
+        assert_eq!(error, EncryptError::OutOfRange { plaintext: 101_f64, in_range: ValueRange::new(0_f64, 100_f64) });
+    }
+
+    #[test]
+    fn test_decrypt_batch () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+
+        let plaintexts: Vec<f64> = vec![5_f64, 0_f64, 100_f64, 5_f64, 42_f64];
+        let ciphertexts: Vec<f64> = ope.encrypt_batch(&plaintexts).unwrap();
+        assert_eq!(ope.decrypt_batch(&ciphertexts), Ok(plaintexts));
+
+        assert_eq!(ope.decrypt_batch(&[]), Ok(vec![]));
+
+        // Out of range ciphertexts fail the batch upfront, wherever they are
+        let error: DecryptError = ope.decrypt_batch(&[ciphertexts[0], 1001_f64, 2.5_f64]).unwrap_err();
+        assert_eq!(error, DecryptError::OutOfRange { ciphertext: 1001_f64, out_range: ValueRange::new(0_f64, 1000_f64) });
+        assert_eq!(ope.decrypt_batch(&[ciphertexts[0], 2.5_f64]), Err(DecryptError::NotInteger(2.5_f64)));
+    }
+
+    #[test]
+    fn test_plaintext_distance () {
+        let ope: OPE = create_ope("key", -50_f64, 50_f64, 0_f64, 10_000_f64);
+        let ciphertexts: Vec<f64> = (-50..=50).map(|plaintext| ope.encrypt(&(plaintext as f64))).collect();
+
+        for pair in ciphertexts.windows(2) {
+            assert_eq!(ope.plaintext_distance(&pair[0], &pair[1]), 1_f64);
+            assert_eq!(ope.plaintext_distance(&pair[1], &pair[0]), 1_f64);
+        }
+        assert_eq!(ope.plaintext_distance(&ciphertexts[0], &ciphertexts[100]), 100_f64);
+        assert_eq!(ope.plaintext_distance(&ciphertexts[7], &ciphertexts[7]), 0_f64);
+
+        // The second ciphertext is checked before the first is decrypted
+        assert_eq!(ope.try_plaintext_distance(&ciphertexts[0], &10_001_f64), Err(DecryptError::OutOfRange { ciphertext: 10_001_f64, out_range: ValueRange::new(0_f64, 10_000_f64) }));
+        assert_eq!(ope.try_plaintext_distance(&-1_f64, &ciphertexts[0]), Err(DecryptError::OutOfRange { ciphertext: -1_f64, out_range: ValueRange::new(0_f64, 10_000_f64) }));
+        assert_eq!(ope.try_plaintext_distance(&ciphertexts[0], &0.5_f64), Err(DecryptError::NotInteger(0.5_f64)));
+    }
+
+    #[test]
+    fn test_encrypt_iter () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+
+        // Chained over a range iterator, only even plaintexts being encrypted
+        let ciphertexts: Vec<f64> = ope.encrypt_iter(ValueRange::new(0_f64, 100_f64).iter().filter(|plaintext| plaintext % 2_f64 == 0_f64))
+            .collect::<Result<Vec<f64>, EncryptError>>()
+            .unwrap();
+        assert_eq!(ciphertexts.len(), 51);
+        assert_eq!(ciphertexts[21], ope.encrypt(&42_f64));
+        assert!(ciphertexts.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // Errors are yielded in place, without stopping the iteration
+        let results: Vec<Result<f64, EncryptError>> = ope.encrypt_iter(vec![5_f64, 101_f64, 0_f64].into_iter()).collect();
+        assert_eq!(results, vec![Ok(ope.encrypt(&5_f64)), Err(EncryptError::OutOfRange { plaintext: 101_f64, in_range: ValueRange::new(0_f64, 100_f64) }), Ok(ope.encrypt(&0_f64))]);
+
+        // Nothing is encrypted until the iterator is consumed
+        let mut lazy = ope.encrypt_iter((0..).map(|plaintext| plaintext as f64));
+        assert_eq!(lazy.next(), Some(Ok(ope.encrypt(&0_f64))));
+        assert!(lazy.nth(99).unwrap().is_ok());
+        assert!(lazy.next().unwrap().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_encrypt_par () {
+        let ope: OPE = create_ope("key", 0_f64, 10_000_f64, 0_f64, 1_000_000_f64);
+        let plaintexts: Vec<f64> = (0..2_000).map(|index| ((index * 7_919) % 10_001) as f64).collect();
+
+        let sequential: Vec<f64> = plaintexts.iter().map(|plaintext| ope.encrypt(plaintext)).collect();
+        assert_eq!(ope.encrypt_par(&plaintexts), sequential);
+        assert!(ope.encrypt_par(&[]).is_empty());
+
+        assert_eq!(ope.decrypt_par(&sequential), plaintexts);
+        assert!(ope.decrypt_par(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_encrypt_batch_cached () {
+        let ope: OPE = create_ope("key", 0_f64, 1000_f64, 0_f64, 1_000_000_f64);
+        let plaintexts: Vec<f64> = (0..200).map(|index| (index * 5) as f64).collect();
+        let expected: Vec<f64> = ope.encrypt_batch(&plaintexts).unwrap();
+
+        let mut cache: TapeCache = TapeCache::new();
+        assert_eq!(ope.encrypt_batch_cached(&plaintexts, &mut cache), Ok(expected.clone()));
+        assert!(!cache.is_empty());
+
+        // A second batch only reads the cache
+        let size: usize = cache.len();
+        assert_eq!(ope.encrypt_batch_cached(&plaintexts[50..100], &mut cache), Ok(expected[50..100].to_vec()));
+        assert_eq!(cache.len(), size);
+
+        // Tapes of another key are dropped rather than reused
+        let other: OPE = create_ope("another key", 0_f64, 1000_f64, 0_f64, 1_000_000_f64);
+        assert_eq!(other.encrypt_batch_cached(&plaintexts, &mut cache), other.encrypt_batch(&plaintexts));
+        assert_eq!(ope.encrypt_batch_cached(&plaintexts, &mut cache), Ok(expected));
+
+        cache.clear();
+        assert!(cache.is_empty());
+
+        let error: EncryptError = ope.encrypt_batch_cached(&[5_f64, 1001_f64], &mut cache).unwrap_err();
+        assert_eq!(error, EncryptError::OutOfRange { plaintext: 1001_f64, in_range: ValueRange::new(0_f64, 1000_f64) });
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_encrypt_preserves_order () {
+        for (in_start, in_end, out_start, out_end) in [(0_f64, 500_f64, 0_f64, 100_000_f64), (-250_f64, 250_f64, -501_f64, 500_f64)].iter() {
+            let ope: OPE = create_ope("key", *in_start, *in_end, *out_start, *out_end);
+            let in_range: ValueRange = ValueRange::new(*in_start, *in_end);
+
+            let ciphertexts: Vec<f64> = in_range.iter().map(|plaintext| ope.encrypt(&plaintext)).collect();
+            for (index, pair) in ciphertexts.windows(2).enumerate() {
+                assert!(pair[0] < pair[1], "encrypt({}) = {} is not below encrypt({}) = {}", in_start + index as f64, pair[0], in_start + index as f64 + 1_f64, pair[1]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encrypt_preserves_order_random () {
+        // Random keys, ranges and pairs of plaintexts, from a fixed seed so
+        // that a failing case can be replayed. in_range holds at most 10^4
+        // values, and out_range 10^9, to keep the test short.
+        let mut seed: u64 = 42;
+        let mut next = |bound: u64| -> u64 { (lcg_next(&mut seed) >> 33) % bound };
+
+        for case in 0..200 {
+            let key: String = format!("key {}", next(u64::MAX));
+            let in_start: f64 = next(20_000) as f64 - 10_000_f64;
+            let in_size: f64 = next(10_000) as f64 + 1_f64;
+            let out_start: f64 = next(2_000_000_000) as f64 - 1_000_000_000_f64;
+            let out_size: f64 = in_size + next(1_000_000_000) as f64;
+
+            let ope: OPE = create_ope(&key, in_start, in_start + in_size - 1_f64, out_start, out_start + out_size - 1_f64);
+
+            for _ in 0..5 {
+                let a: f64 = in_start + next(in_size as u64) as f64;
+                let b: f64 = in_start + next(in_size as u64) as f64;
+                let (ciphertext_a, ciphertext_b): (f64, f64) = (ope.encrypt(&a), ope.encrypt(&b));
+
+                assert_eq!(a.partial_cmp(&b), ciphertext_a.partial_cmp(&ciphertext_b), "case {} : encrypt({}) = {} and encrypt({}) = {} with {:?}", case, a, ciphertext_a, b, ciphertext_b, ope.config());
+                assert_eq!(ope.encrypt(&a), ciphertext_a);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encrypt_negative_out_range () {
+        // mid is out_range.start - 1 + ceil(out_size / 2) : the lower half
+        // gets the extra value of odd sizes, whatever the sign of the bounds
+        let ope: OPE = create_ope("key", 0_f64, 99_f64, -1000_f64, -1_f64);
+        assert_eq!(ope.encrypt_traced(&0_f64).1[0].mid, -501_f64);
+        let odd: OPE = create_ope("key", 0_f64, 99_f64, -999_f64, -1_f64);
+        assert_eq!(odd.encrypt_traced(&0_f64).1[0].mid, -500_f64);
+
+        let mut previous: f64 = f64::NEG_INFINITY;
+        for plaintext in 0..100 {
+            let plaintext: f64 = plaintext as f64;
+            let (ciphertext, trace): (f64, Vec<TapeStep>) = ope.encrypt_traced(&plaintext);
+
+            // Replay the descent, every half being an integral range of the
+            // previous one
+            let mut out_range: ValueRange = ValueRange::new(-1000_f64, -1_f64);
+            let (mut left, mut right): (u32, u32) = (0, 0);
+            for step in trace.iter() {
+                assert_eq!(step.mid, step.mid.floor());
+                assert_eq!(step.mid, out_range.start - 1_f64 + (out_range.size() / 2_f64).ceil());
+                let (lower, upper): (ValueRange, ValueRange) = out_range.split_at(step.mid);
+                if plaintext <= step.x {
+                    out_range = lower;
+                    left += 1;
+                } else {
+                    out_range = upper;
+                    right += 1;
+                }
+            }
+
+            assert!(out_range.contains(&ciphertext));
+            assert!(ciphertext > previous && ciphertext <= -1_f64);
+            assert_eq!(ope.decrypt(&ciphertext), plaintext);
+            if plaintext == 42_f64 {
+                assert!(left >= 2 && right >= 2, "{} left and {} right descents", left, right);
+            }
+            previous = ciphertext;
+        }
+    }
+
+    #[test]
+    fn test_encrypt_traced () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+
+        for plaintext in [0_f64, 5_f64, 42_f64, 100_f64].iter() {
+            let (ciphertext, trace): (f64, Vec<TapeStep>) = ope.encrypt_traced(plaintext);
+            assert_eq!(ciphertext, ope.encrypt(plaintext));
+
+            // Replay the partition from the trace, which must narrow in_range
+            // down to the plaintext in exactly as many steps as recorded
+            let mut in_range: ValueRange = ValueRange::new(0_f64, 100_f64);
+            let mut out_range: ValueRange = ValueRange::new(0_f64, 1000_f64);
+            for step in trace.iter() {
+                assert!(in_range.size() > 1_f64);
+                assert!(out_range.contains(&step.mid));
+
+                if *plaintext <= step.x {
+                    in_range = ValueRange::new(in_range.start, step.x);
+                    out_range = ValueRange::new(out_range.start, step.mid);
+                } else {
+                    in_range = ValueRange::new(step.x + 1_f64, in_range.end);
+                    out_range = ValueRange::new(step.mid + 1_f64, out_range.end);
+                }
+            }
+
+            assert_eq!(in_range, ValueRange::new(*plaintext, *plaintext));
+            assert!(out_range.contains(&ciphertext));
+        }
+
+        // A single plaintext needs no partition at all
+        let ope: OPE = create_ope("key", 7_f64, 7_f64, 0_f64, 10_f64);
+        assert_eq!(ope.encrypt_traced(&7_f64).1, vec![]);
+    }
+
+    #[test]
+    fn test_partition_points_are_integers () {
+        // Odd and even sizes, negative bounds and bounds close to 2^52, where
+        // halving out_range could leave a fractional middle if it were not
+        // rounded up
+        let ranges: [(f64, f64, f64, f64); 6] = [
+            (0_f64, 10_f64, 0_f64, 11_f64),
+            (-7_f64, 8_f64, -1001_f64, 1000_f64),
+            (1_f64, 2_f64, 3_f64, 4_f64),
+            (0_f64, 999_f64, 0_f64, 2_f64.powi(40) - 2_f64),
+            (-2_f64.powi(51), -2_f64.powi(51) + 100_f64, 2_f64.powi(52) - 1_000_001_f64, 2_f64.powi(52) - 1_f64),
+            (2_f64.powi(52) - 1_000_f64, 2_f64.powi(52) - 1_f64, -2_f64.powi(52) + 1_f64, 2_f64.powi(52) - 1_f64),
+        ];
+
+        for (in_start, in_end, out_start, out_end) in ranges.iter() {
+            let ope: OPE = create_ope("key", *in_start, *in_end, *out_start, *out_end);
+            let size: f64 = in_end - in_start;
+
+            for offset in [0_f64, 1_f64, (size / 3_f64).floor(), (size / 2_f64).ceil(), size].iter() {
+                let plaintext: f64 = in_start + offset;
+                let (ciphertext, trace): (f64, Vec<TapeStep>) = ope.encrypt_traced(&plaintext);
+
+                assert!(trace.iter().all(|step| step.mid == step.mid.floor() && step.x == step.x.floor()), "{:?}", (in_start, in_end, out_start, out_end));
+                assert_eq!(ciphertext, ciphertext.floor());
+                assert_eq!(ope.decrypt(&ciphertext), plaintext);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decrypt () {
+        let configurations = [
+            ("key", 0_f64, 5_f64, 0_f64, 15_f64),
+            ("another key", 0_f64, 5_f64, 0_f64, 15_f64),
+            ("another key", 0_f64, 10_f64, 0_f64, 20_f64),
+            ("a much longer encryption key for testing", 0_f64, 10_f64, 0_f64, 20_f64),
+            ("a much longer encryption key for testing", -5_f64, 5_f64, -10_f64, 10_f64),
+            ("key", -5_f64, 5_f64, -10_f64, 10_f64),
+            ("key", 0_f64, 100_f64, 0_f64, 1000_f64),
+            ("key", 0_f64, 50_f64, 0_f64, 50_f64),
+        ];
+
+        for (key, in_start, in_end, out_start, out_end) in configurations.iter() {
+            let ope: OPE = create_ope(key, *in_start, *in_end, *out_start, *out_end);
+
+            let mut plaintext: f64 = *in_start;
+            while plaintext <= *in_end {
+                assert_eq!(ope.decrypt(&ope.encrypt(&plaintext)), plaintext);
+                plaintext += 1_f64;
+            }
+        }
+    }
+
+    #[test]
+    fn test_decrypt_many_rejections () {
+        // With the decimal tape encoding, these plaintexts need more than 10
+        // HRUA* iterations at some step of the partition, which used to make
+        // encryption panic
+        let ope: OPE = create_ope("a much longer encryption key for testing", 0_f64, 1000_f64, 0_f64, 1_000_000_f64);
+
+        let ciphertexts: Vec<f64> = [174_f64, 175_f64, 200_f64, 911_f64, 913_f64].iter().map(|plaintext| ope.encrypt(plaintext)).collect();
+        assert!(ciphertexts.windows(2).all(|pair| pair[0] < pair[1]));
+
+        for plaintext in [175_f64, 200_f64, 911_f64].iter() {
+            assert_eq!(ope.decrypt(&ope.encrypt(plaintext)), *plaintext);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "does not match any plaintext")]
+    fn test_decrypt_invalid_ciphertext () {
+        // 1 and 2 encrypt to 1 and 8 (3 and 8 with the decimal tape), so 2 is
+        // not a valid ciphertext
+        let ope: OPE = create_ope("key", 0_f64, 5_f64, 0_f64, 15_f64);
+        ope.decrypt(&2_f64);
+    }
+
+    #[test]
+    #[should_panic(expected = "should be in out_range")]
+    fn test_decrypt_out_of_range () {
+        let ope: OPE = create_ope("key", 0_f64, 5_f64, 0_f64, 15_f64);
+        ope.decrypt(&16_f64);
+    }
+
+    #[test]
+    fn test_try_decrypt () {
+        let ope: OPE = create_ope("key", 0_f64, 5_f64, 0_f64, 15_f64);
+        assert_eq!(ope.try_decrypt(&ope.encrypt(&3_f64)), Ok(3_f64));
+
+        // Every integer of out_range which is not a ciphertext, whether it
+        // falls in an empty half of the partition or not
+        let ciphertexts: Vec<f64> = ope.config().in_range.iter().map(|plaintext| ope.encrypt(&plaintext)).collect();
+        for value in ope.config().out_range.iter().filter(|value| !ciphertexts.contains(value)) {
+            assert_eq!(ope.try_decrypt(&value), Err(DecryptError::NoMatch(value)));
+        }
+
+        let error: DecryptError = ope.try_decrypt(&16_f64).unwrap_err();
+        assert_eq!(error, DecryptError::OutOfRange { ciphertext: 16_f64, out_range: ValueRange::new(0_f64, 15_f64) });
+        assert_eq!(format!("{}", error), "OPE : ciphertext (16) should be in out_range ValueRange { start: 0.0, end: 15.0 }.");
+        assert_eq!(ope.try_decrypt(&5.5_f64), Err(DecryptError::NotInteger(5.5_f64)));
+        assert!(matches!(ope.try_decrypt(&f64::NAN), Err(DecryptError::OutOfRange { .. })));
+
+        let wide: OPE = OPE::new_wide("key", &ValueRange::new(0_f64, 5_f64), &ValueRange::new(0_f64, 2_f64.powi(60)));
+        assert!(matches!(wide.try_decrypt(&0_f64), Err(DecryptError::WideOutRange(_))));
+    }
+
+    #[test]
+    fn test_encrypt_range_query () {
+        let ope: OPE = create_ope("key", 0_f64, 1000_f64, 0_f64, 1_000_000_f64);
+        let (lo, hi): (f64, f64) = ope.encrypt_range_query(&100_f64, &200_f64);
+
+        assert_eq!((lo, hi), (ope.encrypt(&100_f64), ope.encrypt(&200_f64)));
+        for plaintext in ope.config().in_range.iter() {
+            let ciphertext: f64 = ope.encrypt(&plaintext);
+            assert_eq!(lo <= ciphertext && ciphertext <= hi, (100_f64..=200_f64).contains(&plaintext));
+        }
+
+        assert_eq!(ope.encrypt_range_query(&5_f64, &5_f64), (ope.encrypt(&5_f64), ope.encrypt(&5_f64)));
+        assert_eq!(ope.try_encrypt_range_query(&200_f64, &100_f64), Err(EncryptError::InvalidQuery { lo: 200_f64, hi: 100_f64 }));
+        assert!(matches!(ope.try_encrypt_range_query(&0_f64, &1001_f64), Err(EncryptError::OutOfRange { .. })));
+        assert!(matches!(ope.try_encrypt_range_query(&f64::NAN, &10_f64), Err(EncryptError::NonFinite(_))));
+    }
+
+    #[test]
+    fn test_cipher_bounds () {
+        let ope: OPE = create_ope("key", -50_f64, 200_f64, 0_f64, 100_000_f64);
+        let (min, max): (f64, f64) = ope.cipher_bounds();
+
+        assert_eq!((min, max), (ope.encrypt(&-50_f64), ope.encrypt(&200_f64)));
+        for plaintext in -49..200 {
+            let ciphertext: f64 = ope.encrypt(&(plaintext as f64));
+            assert!(min < ciphertext && ciphertext < max);
+        }
+
+        let ope: OPE = create_ope("key", 7_f64, 7_f64, 0_f64, 100_f64);
+        assert_eq!(ope.cipher_bounds(), (ope.encrypt(&7_f64), ope.encrypt(&7_f64)));
+    }
+
+    #[test]
+    fn test_encrypt_to_bytes () {
+        let ope: OPE = create_ope("key", -100_f64, 100_f64, -40_000_f64, 40_000_f64);
+
+        // 80000 offsets take 17 bits, written with 3 bytes
+        let encrypted: Vec<Vec<u8>> = (-100..=100).map(|plaintext| ope.encrypt_to_bytes(&(plaintext as f64))).collect();
+        assert!(encrypted.iter().all(|bytes| bytes.len() == 3));
+        for pair in encrypted.windows(2) {
+            assert!(pair[0] < pair[1]);
+        }
+
+        for plaintext in [-100_f64, -1_f64, 0_f64, 42_f64, 100_f64] {
+            let bytes: Vec<u8> = ope.encrypt_to_bytes(&plaintext);
+            let offset: f64 = ope.encrypt(&plaintext) + 40_000_f64;
+            assert_eq!(bytes, (offset as u64).to_be_bytes()[5..].to_vec());
+            assert_eq!(ope.decrypt_from_bytes(&bytes), plaintext);
+        }
+
+        // Widths follow the size of out_range
+        assert_eq!(create_ope("key", 0_f64, 0_f64, 5_f64, 5_f64).encrypt_to_bytes(&0_f64), vec![0]);
+        assert_eq!(create_ope("key", 0_f64, 10_f64, 0_f64, 255_f64).encrypt_to_bytes(&5_f64).len(), 1);
+        assert_eq!(create_ope("key", 0_f64, 10_f64, 0_f64, 256_f64).encrypt_to_bytes(&5_f64).len(), 2);
+        assert_eq!(create_ope("key", 0_f64, 10_f64, 0_f64, 2_f64.powi(53) - 1_f64).encrypt_to_bytes(&5_f64).len(), 7);
+
+        assert_eq!(ope.try_decrypt_from_bytes(&[0, 1]), Err(DecryptError::InvalidLength { len: 2, expected: 3 }));
+        assert_eq!(ope.try_decrypt_from_bytes(&[255, 255, 255]), Err(DecryptError::OutOfRange { ciphertext: 16_737_215_f64, out_range: ValueRange::new(-40_000_f64, 40_000_f64) }));
+        assert!(matches!(ope.try_encrypt_to_bytes(&101_f64), Err(EncryptError::OutOfRange { .. })));
+    }
+
+    #[test]
+    fn test_shard_of () {
+        let ope: OPE = create_ope("key", 0_f64, 500_f64, 0_f64, 99_999_f64);
+
+        let mut previous: usize = 0;
+        for plaintext in ope.config().in_range.iter() {
+            let shard: usize = ope.shard_of(&ope.encrypt(&plaintext), 7);
+            assert!(shard >= previous && shard < 7);
+            previous = shard;
+        }
+
+        // Equal bands of out_range, the last shard ending with it
+        assert_eq!(ope.shard_of(&0_f64, 4), 0);
+        assert_eq!(ope.shard_of(&24_999_f64, 4), 0);
+        assert_eq!(ope.shard_of(&25_000_f64, 4), 1);
+        assert_eq!(ope.shard_of(&74_999_f64, 4), 2);
+        assert_eq!(ope.shard_of(&99_999_f64, 4), 3);
+        assert_eq!(ope.shard_of(&99_999_f64, 1), 0);
+        assert_eq!(ope.shard_of(&5_f64, 200_000), 10);
+
+        let ope: OPE = create_ope("key", -10_f64, 10_f64, -1000_f64, 999_f64);
+        assert_eq!(ope.shard_of(&-1000_f64, 2), 0);
+        assert_eq!(ope.shard_of(&-1_f64, 2), 0);
+        assert_eq!(ope.shard_of(&0_f64, 2), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "num_shards should be greater than 0")]
+    fn test_shard_of_no_shard () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+        ope.shard_of(&ope.encrypt(&5_f64), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "should be in out_range")]
+    fn test_shard_of_out_of_range () {
+        create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64).shard_of(&1001_f64, 4);
+    }
+
+    #[test]
+    fn test_encrypt_domain () {
+        let ope: OPE = create_ope("key", -50_f64, 200_f64, 0_f64, 100_000_f64);
+        let pairs: Vec<(f64, f64)> = ope.encrypt_domain().unwrap();
+
+        assert_eq!(pairs.len(), 251);
+        assert_eq!(pairs[0], (-50_f64, ope.encrypt(&-50_f64)));
+        for pair in pairs.iter() {
+            assert_eq!(pair.1, ope.encrypt(&pair.0));
+        }
+
+        // Sorted by plaintext, and so by ciphertext
+        for window in pairs.windows(2) {
+            assert_eq!(window[0].0 + 1_f64, window[1].0);
+            assert!(window[0].1 < window[1].1);
+        }
+
+        let large: OPE = create_ope("key", 0_f64, 2_f64.powi(20), 0_f64, 2_f64.powi(40));
+        assert_eq!(large.encrypt_domain(), Err(EncryptError::DomainTooLarge { in_size: 2_f64.powi(20) + 1_f64, limit: 2_f64.powi(20) }));
+    }
+
+    #[test]
+    fn test_decrypt_checked () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 100_000_f64);
+        let ciphertexts: Vec<f64> = ope.config().in_range.iter().map(|plaintext| ope.encrypt(&plaintext)).collect();
+
+        for (plaintext, ciphertext) in ciphertexts.iter().enumerate() {
+            assert_eq!(ope.decrypt_checked(ciphertext), Ok(plaintext as f64));
+
+            // Values next to a ciphertext fall between two of them
+            for corrupted in [ciphertext - 1_f64, ciphertext + 1_f64] {
+                if !ciphertexts.contains(&corrupted) && ope.config().out_range.contains(&corrupted) {
+                    assert_eq!(ope.decrypt_checked(&corrupted), Err(DecryptError::NoMatch(corrupted)));
+                }
+            }
+        }
+
+        assert_eq!(ope.decrypt_checked(&100_001_f64), Err(DecryptError::OutOfRange { ciphertext: 100_001_f64, out_range: ValueRange::new(0_f64, 100_000_f64) }));
+    }
+
+    #[test]
+    fn test_decrypt_with_range () {
+        let in_range: ValueRange = ValueRange::new(0_f64, 100_f64);
+        let narrow: ValueRange = ValueRange::new(0_f64, 1000_f64);
+        let legacy: OPE = OPE::new("key", &in_range, &narrow);
+        let ope: OPE = OPE::new("key", &in_range, &ValueRange::new(0_f64, 1_000_000_f64));
+
+        // Ciphertexts of the narrow out_range are decrypted by the widened OPE
+        for plaintext in [0_f64, 5_f64, 42_f64, 100_f64] {
+            let ciphertext: f64 = legacy.encrypt(&plaintext);
+            assert_eq!(ope.decrypt_with_range(&ciphertext, &narrow), plaintext);
+            assert_eq!(ope.decrypt(&ope.encrypt(&plaintext)), plaintext);
+        }
+
+        assert_eq!(ope.try_decrypt_with_range(&1001_f64, &narrow), Err(DecryptError::OutOfRange { ciphertext: 1001_f64, out_range: narrow.clone() }));
+        assert_eq!(ope.try_decrypt_with_range(&5.5_f64, &narrow), Err(DecryptError::NotInteger(5.5_f64)));
+
+        let error: DecryptError = ope.try_decrypt_with_range(&5_f64, &ValueRange::new(0_f64, 10_f64)).unwrap_err();
+        assert_eq!(error, DecryptError::InRangeTooLarge { in_size: 101_f64, out_size: 11_f64 });
+        assert_eq!(format!("{}", error), "OPE : in_range size (101) should not be greater than out_range size (11).");
+    }
+
+    #[test]
+    #[should_panic(expected = "OPE : ciphertext should be an integer. Found 5.5")]
+    fn test_decrypt_not_integer () {
+        let ope: OPE = create_ope("key", 0_f64, 5_f64, 0_f64, 15_f64);
+        ope.decrypt(&5.5_f64);
+    }
+
+    #[test]
+    fn test_decrypt_quantile () {
+        let ope: OPE = create_ope("key", 0_f64, 1000_f64, 0_f64, 100_000_f64);
+        let plaintexts: Vec<f64> = [917_f64, 3_f64, 500_f64, 42_f64, 42_f64, 1000_f64, 77_f64, 250_f64, 0_f64, 600_f64, 333_f64].to_vec();
+
+        let mut ciphertexts: Vec<f64> = plaintexts.iter().map(|plaintext| ope.encrypt(plaintext)).collect();
+        ciphertexts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mut sorted_plaintexts: Vec<f64> = plaintexts.clone();
+        sorted_plaintexts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        for (q, index) in [(0_f64, 0), (0.25_f64, 3), (0.5_f64, 5), (0.9_f64, 9), (1_f64, 10)].iter() {
+            assert_eq!(ope.decrypt_quantile(&ciphertexts, *q), sorted_plaintexts[*index], "q = {}", q);
+        }
+        assert_eq!(ope.decrypt_quantile(&ciphertexts, 0.5_f64), 250_f64);
+    }
+
+    #[test]
+    #[should_panic(expected = "OPE : quantile (1.5) should be between 0 and 1.")]
+    fn test_decrypt_quantile_invalid () {
+        let ope: OPE = create_ope("key", 0_f64, 5_f64, 0_f64, 15_f64);
+        ope.decrypt_quantile(&[ope.encrypt(&1_f64)], 1.5_f64);
+    }
+
+    #[test]
+    fn test_rotation_plan () {
+        let old: OPE = create_ope("old key", 0_f64, 50_f64, 0_f64, 1000_f64);
+        let new: OPE = create_ope("new key", 0_f64, 50_f64, 0_f64, 1000_f64);
+        let plan: Vec<(f64, f64)> = old.rotation_plan(b"new key").collect();
+
+        assert_eq!(plan.len(), 51);
+        for (plaintext, (old_ciphertext, new_ciphertext)) in plan.iter().enumerate() {
+            assert_eq!(old.decrypt(old_ciphertext), plaintext as f64);
+            assert_eq!(*new_ciphertext, new.encrypt(&(plaintext as f64)));
+        }
+
+        // Both columns of the plan are in increasing order
+        assert!(plan.windows(2).all(|pair| pair[0].0 < pair[1].0 && pair[0].1 < pair[1].1));
+        assert_ne!(plan.iter().map(|pair| pair.0).collect::<Vec<f64>>(), plan.iter().map(|pair| pair.1).collect::<Vec<f64>>());
+    }
+
+    #[test]
+    fn test_is_valid_ciphertext () {
+        let ope: OPE = create_ope("key", 0_f64, 5_f64, 0_f64, 15_f64);
+
+        for ciphertext in [0_f64, 1_f64, 8_f64, 15_f64, ope.encrypt(&3_f64)].iter() {
+            assert!(ope.is_valid_ciphertext(ciphertext), "{}", ciphertext);
+        }
+
+        for ciphertext in [0.5_f64, 14.999_f64, -1_f64, 16_f64, 1e300_f64, f64::NAN, f64::INFINITY, f64::NEG_INFINITY].iter() {
+            assert!(!ope.is_valid_ciphertext(ciphertext), "{}", ciphertext);
+        }
+    }
+
+    #[test]
+    fn test_reencrypt () {
+        let old: OPE = create_ope("old key", 0_f64, 100_f64, 0_f64, 1000_f64);
+        let new: OPE = create_ope("new key", 0_f64, 100_f64, 0_f64, 1_000_000_f64);
+
+        let ciphertexts: Vec<f64> = [3_f64, 42_f64, 43_f64, 99_f64].iter().map(|plaintext| old.encrypt(plaintext)).collect();
+        let migrated: Vec<f64> = ciphertexts.iter().map(|ciphertext| old.reencrypt(ciphertext, &new)).collect();
+
+        assert_eq!(migrated, vec![new.encrypt(&3_f64), new.encrypt(&42_f64), new.encrypt(&43_f64), new.encrypt(&99_f64)]);
+        assert!(migrated.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(new.decrypt(&migrated[1]), 42_f64);
+    }
+
+    #[test]
+    #[should_panic(expected = "should be in in_range")]
+    fn test_reencrypt_narrower_in_range () {
+        let old: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+        let new: OPE = create_ope("key", 0_f64, 50_f64, 0_f64, 1000_f64);
+        old.reencrypt(&old.encrypt(&99_f64), &new);
+    }
+
+    #[test]
+    fn test_with_generator () {
+        let in_range: ValueRange = ValueRange::new(0_f64, 100_f64);
+        let out_range: ValueRange = ValueRange::new(0_f64, 1_000_000_f64);
+        let ope: OPE<f64, HmacSha512AesCtr> = OPE::with_generator("key", &in_range, &out_range, HmacSha512AesCtr).unwrap();
+        let other: OPE<f64, HmacSha512AesCtr> = OPE::with_generator("key", &in_range, &out_range, HmacSha512AesCtr).unwrap();
+
+        let ciphertexts: Vec<f64> = (0..=100).map(|plaintext| ope.encrypt(&(plaintext as f64))).collect();
+        assert_eq!(ciphertexts, (0..=100).map(|plaintext| other.encrypt(&(plaintext as f64))).collect::<Vec<f64>>());
+        assert!(ciphertexts.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(ope.decrypt(&ciphertexts[42]), 42_f64);
+        assert_eq!(ope.tape_gen(&1_f64), HmacSha512AesCtr.coins(b"key", 1_f64));
+
+        // Tapes differ from the default generator ones, and so do ciphertexts
+        let default: OPE = OPE::new("key", &in_range, &out_range);
+        assert_ne!(ope.tape_gen(&1_f64), default.tape_gen(&1_f64));
+        assert_ne!(ciphertexts, (0..=100).map(|plaintext| default.encrypt(&(plaintext as f64))).collect::<Vec<f64>>());
+
+        // Ciphertexts can be moved from one generator to the other
+        assert_eq!(default.reencrypt(&default.encrypt(&42_f64), &ope), ciphertexts[42]);
+    }
+
+    #[test]
+    fn test_compare_cipher () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+        let (small, large): (f64, f64) = (ope.encrypt(&10_f64), ope.encrypt(&90_f64));
+
+        assert_eq!(ope.compare_cipher(&small, &large), Ordering::Less);
+        assert_eq!(ope.compare_cipher(&large, &small), Ordering::Greater);
+        assert_eq!(ope.compare_cipher(&small, &ope.encrypt(&10_f64)), Ordering::Equal);
+        assert_eq!(ope.compare_cipher(&0_f64, &1000_f64), Ordering::Less);
+    }
+
+    #[test]
+    #[should_panic(expected = "OPE : ciphertext (1001) should be in out_range ValueRange { start: 0.0, end: 1000.0 }.")]
+    fn test_compare_cipher_out_of_range () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+        ope.compare_cipher(&500_f64, &1001_f64);
+    }
+
+    #[test]
+    #[should_panic(expected = "OPE : ciphertext (NaN) should be in out_range")]
+    fn test_compare_cipher_nan () {
+        let ope: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+        ope.compare_cipher(&f64::NAN, &500_f64);
+    }
+
+    #[test]
+    fn test_from_bounds () {
+        let ope: OPE<u32> = OPE::from_bounds("key", 0_u32, 100_u32, 0_u64, 1000_u64).unwrap();
+        assert_eq!(ope.config(), OpeConfig { in_range: ValueRange::new(0_f64, 100_f64), out_range: ValueRange::new(0_f64, 1000_f64) });
+
+        assert_eq!(OPE::<u32>::from_bounds("key", 0_u32, 100_u32, 0_u64, 10_u64).unwrap_err(), OpeError::InRangeTooLarge { in_size: 101_f64, out_size: 11_f64 });
+        assert_eq!(OPE::<i64>::from_bounds("key", 5_i64, 0_i64, 0_i128, 10_i128).unwrap_err(), OpeError::InvalidRange(RangeError::StartGreaterThanEnd { start: 5_f64, end: 0_f64 }));
+
+        // Every plaintext bound is accepted, ciphertext bounds up to 2^126 in magnitude
+        assert!(OPE::<u64>::from_bounds("key", 0_u64, u64::MAX, 0_u128, (1_u128 << 126) - 1).is_ok());
+        assert!(OPE::<i64>::from_bounds("key", i64::MIN, i64::MAX, 1 - (1_i128 << 126), (1_i128 << 126) - 1).is_ok());
+
+        let error: OpeError = OPE::<u64>::from_bounds("key", 0_u64, 100_u64, 0_u128, 1_u128 << 126).unwrap_err();
+        assert_eq!(error, OpeError::InexactBound(2_f64.powi(126)));
+        assert_eq!(format!("{}", error), "OPE : bound (85070591730234620000000000000000000000) is too large to be handled exactly, its magnitude should be below 2^126.");
+        assert_eq!(OPE::<i64>::from_bounds("key", i64::MIN, 0_i64, i128::MIN, 0_i128).unwrap_err(), OpeError::InexactBound(-2_f64.powi(127)));
+        assert_eq!(OPE::<u64>::from_bounds("key", 0_u64, 100_u64, 0_u128, u128::MAX).unwrap_err(), OpeError::InexactBound(2_f64.powi(128)));
+    }
+
+    #[test]
+    fn test_encrypt_value_u32 () {
+        let ope: OPE<u32> = OPE::from_bounds("key", 0_u32, 100_u32, 0_u64, 1000_u64).unwrap();
+        let reference: OPE = create_ope("key", 0_f64, 100_f64, 0_f64, 1000_f64);
+
+        let ciphertexts: Vec<u64> = (0_u32..=100_u32).map(|plaintext| ope.encrypt_value(plaintext).unwrap()).collect();
+        assert!(ciphertexts.windows(2).all(|pair| pair[0] < pair[1]));
+
+        for (plaintext, ciphertext) in (0_u32..=100_u32).zip(ciphertexts.iter()) {
+            assert_eq!(*ciphertext as f64, reference.encrypt(&(plaintext as f64)));
+            assert_eq!(ope.decrypt_value(*ciphertext), plaintext);
+        }
+
+        assert_eq!(ope.encrypt_value(101_u32), Err(EncryptError::OutOfRange { plaintext: 101_f64, in_range: ValueRange::new(0_f64, 100_f64) }));
+    }
+
+    #[test]
+    fn test_encrypt_value_u64 () {
+        // Plaintexts and ciphertexts beyond the u32 range, out_range being
+        // more than 2^32 times larger than in_range
+        let start: u64 = 1_u64 << 40;
+        let ope: OPE<u64> = OPE::from_bounds("key", start, start + (1_u64 << 20), 0_u128, (1_u128 << 52) - 1).unwrap();
+
+        let mut previous: u128 = 0_u128;
+        for plaintext in (start..=(start + (1_u64 << 20))).step_by(10_000) {
+            let ciphertext: u128 = ope.encrypt_value(plaintext).unwrap();
+            assert!(ciphertext > previous);
+            assert_eq!(ope.decrypt_value(ciphertext), plaintext);
+            previous = ciphertext;
+        }
+    }
+
+    #[test]
+    fn test_encrypt_value_i64 () {
+        let ope: OPE<i64> = OPE::from_bounds("key", -50_i64, 50_i64, -1_000_000_i128, 1_000_000_i128).unwrap();
+
+        let mut previous: i128 = i128::MIN;
+        for plaintext in -50_i64..=50_i64 {
+            let ciphertext: i128 = ope.encrypt_value(plaintext).unwrap();
+            assert!(ciphertext > previous);
+            assert_eq!(ope.decrypt_value(ciphertext), plaintext);
+            previous = ciphertext;
+        }
+
+        assert!(ope.encrypt_value(-51_i64).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_value_full_range () {
+        // Every u64 and i64 is a plaintext, and ciphertexts are exact far
+        // beyond 2^53
+        let ope: OPE<u64> = OPE::from_bounds("key", 0_u64, u64::MAX, 0_u128, (1_u128 << 100) - 1).unwrap();
+        let plaintexts: Vec<u64> = vec![0, 1, 2, (1 << 53) - 1, 1 << 53, (1 << 53) + 1, u64::MAX - 1, u64::MAX];
+        let ciphertexts: Vec<u128> = plaintexts.iter().map(|plaintext| ope.encrypt_value(*plaintext).unwrap()).collect();
+
+        assert!(ciphertexts.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", ciphertexts);
+        for (plaintext, ciphertext) in plaintexts.iter().zip(ciphertexts.iter()) {
+            assert_eq!(ope.decrypt_value(*ciphertext), *plaintext);
+        }
+
+        let ope: OPE<i64> = OPE::from_bounds("key", i64::MIN, i64::MAX, -(1_i128 << 100), 1_i128 << 100).unwrap();
+        let plaintexts: Vec<i64> = vec![i64::MIN, i64::MIN + 1, -(1 << 53) - 1, -1, 0, 1, (1 << 53) + 1, i64::MAX];
+        let ciphertexts: Vec<i128> = plaintexts.iter().map(|plaintext| ope.encrypt_value(*plaintext).unwrap()).collect();
+
+        assert!(ciphertexts.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", ciphertexts);
+        for (plaintext, ciphertext) in plaintexts.iter().zip(ciphertexts.iter()) {
+            assert_eq!(ope.decrypt_value(*ciphertext), *plaintext);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "does not match any plaintext")]
+    fn test_decrypt_value_invalid () {
+        let ope: OPE<u64> = OPE::from_bounds("key", 0_u64, u64::MAX, 0_u128, (1_u128 << 100) - 1).unwrap();
+        ope.decrypt_value(ope.encrypt_value(42_u64).unwrap() + 1);
+    }
+}
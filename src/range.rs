@@ -0,0 +1,522 @@
+use core::convert::TryFrom;
+use core::error::Error;
+use core::fmt;
+use core::ops::Range;
+
+#[cfg(not(any(feature = "std", test)))]
+use super::math::FloatMath;
+use super::math::round;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// 2^53, the number of values of the largest range whose size is exact
+const SIZE_LIMIT: f64 = 9_007_199_254_740_992_f64;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RangeError {
+    // Reasons for which a ValueRange cannot be built from given bounds
+    StartGreaterThanEnd { start: f64, end: f64 },
+    NonIntegerStart(f64),
+    NonIntegerEnd(f64),
+    NonFinite(f64),
+    InvalidSplit { mid: f64, start: f64, end: f64 },
+}
+
+impl fmt::Display for RangeError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RangeError::StartGreaterThanEnd { start, end } => write!(f, "ValueRange : start value ({}) should not be greater than end value ({}).", start, end),
+            RangeError::NonIntegerStart(start) => write!(f, "ValueRange : start value should be a 0-decimal f64 number. Found {}", start),
+            RangeError::NonIntegerEnd(end) => write!(f, "ValueRange : end value should be a 0-decimal f64 number. Found {}", end),
+            RangeError::NonFinite(bound) => write!(f, "ValueRange : bounds should be finite f64 numbers. Found {}", bound),
+            RangeError::InvalidSplit { mid, start, end } => write!(f, "ValueRange : split point ({}) should be an integer from start value ({}) to end value ({}) excluded.", mid, start, end),
+        }
+    }
+}
+
+impl Error for RangeError {}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(try_from = "ValueRangeBounds"))]
+pub struct ValueRange {
+    pub(crate) start: f64,
+    pub(crate) end: f64,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct ValueRangeBounds {
+    // Unchecked bounds, validated through ValueRange::try_new when deserializing
+    start: f64,
+    end: f64,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<ValueRangeBounds> for ValueRange {
+    type Error = RangeError;
+
+    fn try_from (bounds: ValueRangeBounds) -> Result<ValueRange, RangeError> {
+        ValueRange::try_new(bounds.start, bounds.end)
+    }
+}
+
+impl From<Range<i64>> for ValueRange {
+    fn from (range: Range<i64>) -> ValueRange {
+        // Same as from_exclusive, range.end being excluded. Panics on an
+        // empty range, and bounds beyond 2^53 are rounded to f64 values.
+        ValueRange::new(range.start as f64, (range.end as i128 - 1) as f64)
+    }
+}
+
+impl ValueRange {
+    pub fn new (start: f64, end: f64) -> ValueRange {
+        ValueRange::try_new(start, end).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_new (start: f64, end: f64) -> Result<ValueRange, RangeError> {
+
+        // NaN compares false with everything, so it is rejected before any
+        // other check
+        if !start.is_finite() {
+            return Err(RangeError::NonFinite(start));
+        }
+
+        if !end.is_finite() {
+            return Err(RangeError::NonFinite(end));
+        }
+
+        if start > end {
+            return Err(RangeError::StartGreaterThanEnd { start, end });
+        }
+
+        if start != start.floor() {
+            return Err(RangeError::NonIntegerStart(start));
+        }
+
+        if end != end.floor() {
+            return Err(RangeError::NonIntegerEnd(end));
+        }
+
+        Ok(ValueRange { start, end })
+    }
+
+    pub fn from_exclusive (start: f64, end_exclusive: f64) -> ValueRange {
+        ValueRange::try_from_exclusive(start, end_exclusive).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_from_exclusive (start: f64, end_exclusive: f64) -> Result<ValueRange, RangeError> {
+
+        // Range of the integers from start to end_exclusive excluded, as with
+        // start..end_exclusive in Rust : ValueRange bounds are both included,
+        // so this gives [start, end_exclusive - 1]. The range must not be
+        // empty, and end_exclusive must be an exact f64 integer (below 2^53
+        // in magnitude) for end_exclusive - 1 to be exact too.
+
+        if !end_exclusive.is_finite() {
+            return Err(RangeError::NonFinite(end_exclusive));
+        }
+
+        ValueRange::try_new(start, end_exclusive - 1_f64)
+    }
+
+    pub fn start (&self) -> f64 {
+        // Lowest value of the range, included
+        self.start
+    }
+
+    pub fn end (&self) -> f64 {
+        // Highest value of the range, included
+        self.end
+    }
+
+    pub fn size (&self) -> f64 {
+        // This function is aimed at returning the number of values
+        // in the current ValueRange object
+        self.checked_size().unwrap_or_else(|| panic!("ValueRange : size should not be greater than 2^53. Current range is {:?}", self))
+    }
+
+    pub fn checked_size (&self) -> Option<f64> {
+        // Number of values in the range, unless it is greater than SIZE_LIMIT.
+        // Bounds are integers, so end - start is exact whenever the exact
+        // difference is below SIZE_LIMIT, and rounds to SIZE_LIMIT or beyond
+        // otherwise.
+        let difference: f64 = self.end - self.start;
+
+        if difference < SIZE_LIMIT {
+            Some(difference + 1_f64)
+        } else {
+            None
+        }
+    }
+
+    pub fn contains (&self, number: &f64) -> bool {
+        self.start <= *number && *number <= self.end
+    }
+
+    pub fn clamp (&self, value: f64) -> f64 {
+        // Nearest value of the range, fractional values being rounded to
+        // the nearest integer first, halves upwards. Meant for inputs to be
+        // encrypted, see OPE::encrypt_saturating. NaN is returned as is, for
+        // encryption to reject it.
+        round(value).clamp(self.start, self.end)
+    }
+
+    pub fn intersection (&self, other: &ValueRange) -> Option<ValueRange> {
+        // Values contained in both ranges, if any
+        let start: f64 = self.start.max(other.start);
+        let end: f64 = self.end.min(other.end);
+
+        if start > end {
+            return None;
+        }
+
+        Some(ValueRange { start, end })
+    }
+
+    pub fn union (&self, other: &ValueRange) -> Option<ValueRange> {
+        // Values contained in either range, if they form a single range.
+        // Bounds are integers, so adjacent ranges such as [0, 4] and [5, 9]
+        // are contiguous as well.
+        if self.start > other.end + 1_f64 || other.start > self.end + 1_f64 {
+            return None;
+        }
+
+        Some(ValueRange { start: self.start.min(other.start), end: self.end.max(other.end) })
+    }
+
+    pub fn split_at (&self, mid: f64) -> (ValueRange, ValueRange) {
+        self.try_split_at(mid).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_split_at (&self, mid: f64) -> Result<(ValueRange, ValueRange), RangeError> {
+
+        // Split the range into [start, mid] and [mid + 1, end], the way
+        // ranges are partitioned during encryption. Both halves must hold at
+        // least one value, so mid must be an integer of [start, end).
+
+        if !(self.start <= mid && mid < self.end) || mid != mid.floor() {
+            return Err(RangeError::InvalidSplit { mid, start: self.start, end: self.end });
+        }
+
+        Ok((ValueRange { start: self.start, end: mid }, ValueRange { start: mid + 1_f64, end: self.end }))
+    }
+
+    pub fn iter (&self) -> ValueRangeIter {
+        // Every integer of the range, in increasing order. This takes as
+        // many steps as the size of the range, so is only meant for small
+        // ranges.
+        //
+        // Beyond 2^53, adding 1 to a value may give the same value back, so
+        // such ranges cannot be stepped through exactly.
+        if self.start < -SIZE_LIMIT || self.end > SIZE_LIMIT {
+            panic!("ValueRange : bounds should not be greater than 2^53 in magnitude to iterate over the range. Current range is {:?}", self);
+        }
+
+        ValueRangeIter { next: self.start, remaining: self.size() as u64 }
+    }
+}
+
+impl IntoIterator for &ValueRange {
+    type Item = f64;
+    type IntoIter = ValueRangeIter;
+
+    fn into_iter (self) -> ValueRangeIter {
+        self.iter()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ValueRangeIter {
+    next: f64,
+    remaining: u64,
+}
+
+impl Iterator for ValueRangeIter {
+    type Item = f64;
+
+    fn next (&mut self) -> Option<f64> {
+        // Counting the values left bounds the iteration by the size of the
+        // range, whatever the values themselves
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let current: f64 = self.next;
+        self.next += 1_f64;
+        self.remaining -= 1;
+
+        Some(current)
+    }
+
+    fn size_hint (&self) -> (usize, Option<usize>) {
+        match usize::try_from(self.remaining) {
+            Ok(remaining) => (remaining, Some(remaining)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::RangeError;
+    use super::ValueRange;
+
+    fn create_value_range (start: f64, end: f64) -> ValueRange {
+        ValueRange::new(start, end)
+    }
+
+    #[test]
+    fn test_try_new () {
+        assert_eq!(ValueRange::try_new(-10_f64, 10_f64), Ok(create_value_range(-10_f64, 10_f64)));
+        assert_eq!(ValueRange::try_new(10_f64, 10_f64), Ok(create_value_range(10_f64, 10_f64)));
+
+        assert_eq!(ValueRange::try_new(10_f64, 0_f64), Err(RangeError::StartGreaterThanEnd { start: 10_f64, end: 0_f64 }));
+        assert_eq!(ValueRange::try_new(0.5_f64, 10_f64), Err(RangeError::NonIntegerStart(0.5_f64)));
+        assert_eq!(ValueRange::try_new(0_f64, 9.5_f64), Err(RangeError::NonIntegerEnd(9.5_f64)));
+    }
+
+    #[test]
+    fn test_try_new_non_finite () {
+        assert_eq!(ValueRange::try_new(f64::NEG_INFINITY, 10_f64), Err(RangeError::NonFinite(f64::NEG_INFINITY)));
+        assert_eq!(ValueRange::try_new(0_f64, f64::INFINITY), Err(RangeError::NonFinite(f64::INFINITY)));
+        assert_eq!(ValueRange::try_new(f64::INFINITY, f64::INFINITY), Err(RangeError::NonFinite(f64::INFINITY)));
+
+        // NaN is never equal to itself, so the error is matched instead
+        assert!(matches!(ValueRange::try_new(f64::NAN, 10_f64), Err(RangeError::NonFinite(bound)) if bound.is_nan()));
+        assert!(matches!(ValueRange::try_new(0_f64, f64::NAN), Err(RangeError::NonFinite(bound)) if bound.is_nan()));
+
+        assert_eq!(format!("{}", RangeError::NonFinite(f64::INFINITY)), "ValueRange : bounds should be finite f64 numbers. Found inf");
+    }
+
+    #[test]
+    fn test_range_error_display () {
+        let error: RangeError = RangeError::StartGreaterThanEnd { start: 10_f64, end: 0_f64 };
+        assert_eq!(format!("{}", error), "ValueRange : start value (10) should not be greater than end value (0).");
+
+        let error: RangeError = RangeError::NonIntegerStart(0.5_f64);
+        assert_eq!(format!("{}", error), "ValueRange : start value should be a 0-decimal f64 number. Found 0.5");
+
+        let error: RangeError = RangeError::NonIntegerEnd(9.5_f64);
+        assert_eq!(format!("{}", error), "ValueRange : end value should be a 0-decimal f64 number. Found 9.5");
+    }
+
+    #[test]
+    #[should_panic(expected = "ValueRange : start value (10) should not be greater than end value (0).")]
+    fn test_new_panics () {
+        create_value_range(10_f64, 0_f64);
+    }
+
+    #[test]
+    fn test_checked_size () {
+        assert_eq!(create_value_range(-10_f64, 10_f64).checked_size(), Some(21_f64));
+        assert_eq!(create_value_range(1e300_f64, 1e300_f64).checked_size(), Some(1_f64));
+
+        // Up to 2^53 values, whatever the bounds
+        assert_eq!(create_value_range(0_f64, 2_f64.powi(53) - 1_f64).checked_size(), Some(2_f64.powi(53)));
+        assert_eq!(create_value_range(-2_f64.powi(52), 2_f64.powi(52) - 1_f64).checked_size(), Some(2_f64.powi(53)));
+        assert_eq!(create_value_range(1_f64, 2_f64.powi(53)).checked_size(), Some(2_f64.powi(53)));
+
+        // One more value cannot be counted exactly
+        assert_eq!(create_value_range(0_f64, 2_f64.powi(53)).checked_size(), None);
+        assert_eq!(create_value_range(-2_f64.powi(52), 2_f64.powi(52)).checked_size(), None);
+        assert_eq!(create_value_range(0_f64, 2_f64.powi(60)).checked_size(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ValueRange : size should not be greater than 2^53.")]
+    fn test_size_panics () {
+        create_value_range(0_f64, 2_f64.powi(53)).size();
+    }
+
+    #[test]
+    fn test_from_exclusive () {
+        assert_eq!(ValueRange::from_exclusive(0.0_f64, 100.0_f64), create_value_range(0_f64, 99_f64));
+        assert_eq!(ValueRange::from_exclusive(0.0_f64, 100.0_f64).size(), 100.0);
+        assert_eq!(ValueRange::from_exclusive(-5_f64, -4_f64).size(), 1.0);
+
+        // start..start holds no value
+        assert_eq!(ValueRange::try_from_exclusive(5_f64, 5_f64), Err(RangeError::StartGreaterThanEnd { start: 5_f64, end: 4_f64 }));
+        assert_eq!(ValueRange::try_from_exclusive(0_f64, 10.5_f64), Err(RangeError::NonIntegerEnd(9.5_f64)));
+        assert_eq!(ValueRange::try_from_exclusive(0_f64, f64::INFINITY), Err(RangeError::NonFinite(f64::INFINITY)));
+    }
+
+    #[test]
+    fn test_from_range () {
+        assert_eq!(ValueRange::from(0..100), ValueRange::from_exclusive(0_f64, 100_f64));
+        assert_eq!(ValueRange::from(-10..10).size(), 20.0);
+
+        let range: ValueRange = (i64::MIN..i64::MIN + 1).into();
+        assert_eq!(range, create_value_range(i64::MIN as f64, i64::MIN as f64));
+    }
+
+    #[test]
+    #[should_panic(expected = "ValueRange : start value (3) should not be greater than end value (2).")]
+    fn test_from_empty_range_panics () {
+        let _: ValueRange = (3..3).into();
+    }
+
+    #[test]
+    fn test_print_debug () {
+        let range: ValueRange = create_value_range(0.0_f64, 100.0_f64);
+        assert_eq!(format!("{:?}", range), "ValueRange { start: 0.0, end: 100.0 }");
+    }
+
+    #[test]
+    fn test_equal () {
+        let range_1: ValueRange = create_value_range(0.0_f64, 100.0_f64);
+        let range_2: ValueRange = create_value_range(0.0_f64, 100.0_f64);
+        assert_eq!(range_1, range_2);
+
+        let range_3: ValueRange = create_value_range(1.0_f64, 100.0_f64);
+        assert!(range_1 != range_3);
+    }
+
+    #[test]
+    fn test_size () {
+        let range: ValueRange = create_value_range(0.0_f64, 100.0_f64);
+        assert_eq!(range.size(), 101.0);
+
+        let range: ValueRange = create_value_range(100.0_f64, 100.0_f64);
+        assert_eq!(range.size(), 1.0);
+    }
+
+    #[test]
+    fn test_contains () {
+        let range: ValueRange = create_value_range(0.0_f64, 100.0_f64);
+
+        assert!(range.contains(&0.0_f64));
+        assert!(range.contains(&100.0_f64));
+        assert!(range.contains(&50.0_f64));
+        assert!(!range.contains(&101.0_f64));
+        assert!(!range.contains(&-1.0_f64));
+    }
+
+    #[test]
+    fn test_clamp () {
+        let range: ValueRange = create_value_range(0_f64, 100_f64);
+
+        assert_eq!(range.clamp(-5_f64), 0_f64);
+        assert_eq!(range.clamp(f64::NEG_INFINITY), 0_f64);
+        assert_eq!(range.clamp(0_f64), 0_f64);
+        assert_eq!(range.clamp(42_f64), 42_f64);
+        assert_eq!(range.clamp(100_f64), 100_f64);
+        assert_eq!(range.clamp(1e300_f64), 100_f64);
+        assert_eq!(range.clamp(f64::INFINITY), 100_f64);
+        assert!(range.clamp(f64::NAN).is_nan());
+
+        // Fractional values
+        assert_eq!(range.clamp(42.3_f64), 42_f64);
+        assert_eq!(range.clamp(42.5_f64), 43_f64);
+        assert_eq!(range.clamp(42.7_f64), 43_f64);
+        assert_eq!(range.clamp(-0.4_f64), 0_f64);
+        assert_eq!(range.clamp(-0.6_f64), 0_f64);
+        assert_eq!(range.clamp(100.4_f64), 100_f64);
+        assert_eq!(range.clamp(0.49999999999999994_f64), 0_f64);
+        assert_eq!(create_value_range(-10_f64, 10_f64).clamp(-2.5_f64), -2_f64);
+        assert_eq!(create_value_range(-10_f64, 10_f64).clamp(-2.6_f64), -3_f64);
+    }
+
+    #[test]
+    fn test_intersection () {
+        let range: ValueRange = create_value_range(0_f64, 10_f64);
+
+        // Overlapping
+        assert_eq!(range.intersection(&create_value_range(5_f64, 20_f64)), Some(create_value_range(5_f64, 10_f64)));
+        assert_eq!(create_value_range(-5_f64, 3_f64).intersection(&range), Some(create_value_range(0_f64, 3_f64)));
+
+        // Nested
+        assert_eq!(range.intersection(&create_value_range(2_f64, 4_f64)), Some(create_value_range(2_f64, 4_f64)));
+        assert_eq!(range.intersection(&create_value_range(-100_f64, 100_f64)), Some(range.clone()));
+
+        // Sharing a single value, adjacent and disjoint
+        assert_eq!(range.intersection(&create_value_range(10_f64, 20_f64)), Some(create_value_range(10_f64, 10_f64)));
+        assert_eq!(range.intersection(&create_value_range(11_f64, 20_f64)), None);
+        assert_eq!(range.intersection(&create_value_range(-20_f64, -1_f64)), None);
+    }
+
+    #[test]
+    fn test_union () {
+        let range: ValueRange = create_value_range(0_f64, 10_f64);
+
+        // Overlapping
+        assert_eq!(range.union(&create_value_range(5_f64, 20_f64)), Some(create_value_range(0_f64, 20_f64)));
+
+        // Nested
+        assert_eq!(range.union(&create_value_range(2_f64, 4_f64)), Some(range.clone()));
+        assert_eq!(create_value_range(2_f64, 4_f64).union(&range), Some(range.clone()));
+
+        // Adjacent ranges leave no integer out
+        assert_eq!(range.union(&create_value_range(11_f64, 20_f64)), Some(create_value_range(0_f64, 20_f64)));
+        assert_eq!(range.union(&create_value_range(-5_f64, -1_f64)), Some(create_value_range(-5_f64, 10_f64)));
+
+        // Disjoint
+        assert_eq!(range.union(&create_value_range(12_f64, 20_f64)), None);
+        assert_eq!(create_value_range(-20_f64, -2_f64).union(&range), None);
+    }
+
+    #[test]
+    fn test_split_at () {
+        let range: ValueRange = create_value_range(-5_f64, 10_f64);
+
+        assert_eq!(range.split_at(2_f64), (create_value_range(-5_f64, 2_f64), create_value_range(3_f64, 10_f64)));
+
+        // The halves meet at mid, and keep the bounds of the range
+        let (low, high): (ValueRange, ValueRange) = range.split_at(2_f64);
+        assert_eq!((low.start(), low.end()), (range.start(), 2_f64));
+        assert_eq!((high.start(), high.end()), (3_f64, range.end()));
+
+        // Split points at both ends leave a single value on one side
+        assert_eq!(range.split_at(-5_f64), (create_value_range(-5_f64, -5_f64), create_value_range(-4_f64, 10_f64)));
+        assert_eq!(range.split_at(9_f64), (create_value_range(-5_f64, 9_f64), create_value_range(10_f64, 10_f64)));
+
+        // But never none
+        for mid in [10_f64, -6_f64, 11_f64, 2.5_f64, f64::NAN].iter() {
+            assert!(range.try_split_at(*mid).is_err(), "{}", mid);
+        }
+        assert!(create_value_range(7_f64, 7_f64).try_split_at(7_f64).is_err());
+
+        let error: RangeError = range.try_split_at(10_f64).unwrap_err();
+        assert_eq!(error, RangeError::InvalidSplit { mid: 10_f64, start: -5_f64, end: 10_f64 });
+        assert_eq!(format!("{}", error), "ValueRange : split point (10) should be an integer from start value (-5) to end value (10) excluded.");
+    }
+
+    #[test]
+    #[should_panic(expected = "ValueRange : split point (10) should be an integer")]
+    fn test_split_at_panics () {
+        create_value_range(-5_f64, 10_f64).split_at(10_f64);
+    }
+
+    #[test]
+    fn test_iter () {
+        let range: ValueRange = create_value_range(-2_f64, 3_f64);
+        let values: Vec<f64> = range.iter().collect();
+
+        assert_eq!(values, vec![-2_f64, -1_f64, 0_f64, 1_f64, 2_f64, 3_f64]);
+        assert_eq!(values.len() as f64, range.size());
+
+        let mut count: usize = 0;
+        for value in &range {
+            assert!(range.contains(&value));
+            count += 1;
+        }
+        assert_eq!(count as f64, range.size());
+
+        assert_eq!(create_value_range(7_f64, 7_f64).iter().collect::<Vec<f64>>(), vec![7_f64]);
+        assert_eq!(range.iter().size_hint(), (6, Some(6)));
+
+        // Up to 2^53, stepping stays exact
+        let limit: f64 = 9_007_199_254_740_992_f64;
+        assert_eq!(create_value_range(limit - 2_f64, limit).iter().collect::<Vec<f64>>(), vec![limit - 2_f64, limit - 1_f64, limit]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ValueRange : bounds should not be greater than 2^53 in magnitude to iterate over the range.")]
+    fn test_iter_beyond_exact_values () {
+        // 2^60 + 1 is 2^60 again, so this would never get to the end
+        let start: f64 = 1_152_921_504_606_846_976_f64;
+        create_value_range(start, start + 256_f64).iter();
+    }
+}
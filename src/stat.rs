@@ -1,45 +1,34 @@
-use super::hgd::HGD;
+#[cfg(feature = "rand")]
+use rand::Rng;
 
-#[derive(Clone, Debug, PartialEq)]
-struct ValueRange {
-    start: f64,
-    end: f64,
-}
+use super::hgd::{HGD, PRNG};
+use super::math::div_euclid;
+pub use super::range::ValueRange;
+use super::util::are_binary;
 
-impl ValueRange {
-    fn new (start: f64, end: f64) -> ValueRange {
+pub fn sample_hgd(in_range: &ValueRange, out_range: &ValueRange, nsample: &f64, seed_coins: &[u8; 128], refill: Option<&mut dyn FnMut() -> [u8; 128]>) -> f64 {
 
-        if start > end {
-            panic!("ValueRange : start value ({}) should not be greater than end value ({}).", start, end);
-        }
+    // Get a sample from the hypergeometric distribution, using the provided bit list (seed coins)
+    // as a source of randomness. Once all the seed coins are consumed, new ones are taken from
+    // refill, and sampling panics if no refill is given.
+    //
+    // Each of the seed coins must be a binary unit (0 or 1). This is checked in debug builds only.
 
-        if start != start.floor() {
-            panic!("ValueRange : start value should be a 0-decimal f64 number. Found {}", start);
-        }
+    debug_assert!(are_binary(seed_coins), "Coins must be binary units. Found {:?}", seed_coins);
 
-        if end != end.floor() {
-            panic!("ValueRange : end value should be a 0-decimal f64 number. Found {}", end);
-        }
+    sample_hgd_prng(in_range, out_range, nsample, &mut PRNG::new(*seed_coins, refill))
+}
 
-        ValueRange { start: start, end: end }
-    }
+#[cfg(feature = "rand")]
+pub fn sample_hgd_with_rng<R: Rng>(in_range: &ValueRange, out_range: &ValueRange, nsample: &f64, rng: &mut R) -> f64 {
 
-    fn size (&self) -> f64 {
-        // This function is aimed at returning the number of values
-        // in the current ValueRange object
-        self.end - self.start + 1.0
-    }
+    // Same as sample_hgd, taking coins from the given RNG instead of a tape,
+    // to study the distribution of samples independently of the coin tapes.
 
-    fn contains (&self, number: &f64) -> bool {
-        self.start <= *number && *number <= self.end
-    }
+    sample_hgd_prng(in_range, out_range, nsample, &mut PRNG::from_rng(rng))
 }
 
-fn sample_hgd(in_range: &ValueRange, out_range: &ValueRange, nsample: &f64, seed_coins: &[u8; 32]) -> f64 {
-
-    // Get a sample from the hypergeometric distribution, using the provided bit list (seed coins)
-    // as a source of randomness.
-
+fn sample_hgd_prng(in_range: &ValueRange, out_range: &ValueRange, nsample: &f64, prng: &mut PRNG) -> f64 {
     let in_size: f64 = in_range.size();
     let out_size: f64 = out_range.size();
 
@@ -51,8 +40,8 @@ fn sample_hgd(in_range: &ValueRange, out_range: &ValueRange, nsample: &f64, seed
         panic!("out_range must have a positive size. Current size is : {:?}", out_size);
     }
 
-    if !(in_range.contains(nsample)) {
-        panic!("nsample must be in in_range. Current nsample is {:?}, current in_range is {:?}.", nsample, in_range);
+    if !(out_range.contains(nsample)) {
+        panic!("nsample must be in out_range. Current nsample is {:?}, current out_range is {:?}.", nsample, out_range);
     }
 
     let nsample_index: f64 = nsample - out_range.start + 1_f64;
@@ -60,10 +49,10 @@ fn sample_hgd(in_range: &ValueRange, out_range: &ValueRange, nsample: &f64, seed
         return in_range.start + nsample_index - 1_f64;
     } 
 
-    let in_sample_num: f64 = HGD::rhyper(&nsample_index, &in_size, &(out_size - in_size), seed_coins); 
+    let in_sample_num: f64 = HGD::rhyper(&nsample_index, &in_size, &(out_size - in_size), prng);
 
     if in_sample_num == 0_f64 {
-        return in_range.start;
+        in_range.start
     } else {
         let in_sample = in_range.start + in_sample_num - 1_f64;
 
@@ -71,14 +60,20 @@ fn sample_hgd(in_range: &ValueRange, out_range: &ValueRange, nsample: &f64, seed
             panic!("Error with in_range value. Current in_range is {:?}", in_range);
         }
 
-        return in_sample;
+        in_sample
     }
 }
 
-fn sample_uniform(in_range: &ValueRange, seed_coins: &[u8; 32]) -> f64 {
+pub fn sample_uniform(in_range: &ValueRange, seed_coins: &[u8; 128]) -> f64 {
 
     // Uniformly select a number from the range using the provided bit list (seed_coins)
-    // as a source of randomness.
+    // as a source of randomness. Each coin halves the range, so the 128 coins of a tape
+    // are enough for any range of f64 values.
+    //
+    // Each of the seed coins must be a binary unit (0 or 1). This is checked upfront in
+    // debug builds, and for the coins actually consumed in release builds.
+
+    debug_assert!(are_binary(seed_coins), "Coins must be binary units. Found {:?}", seed_coins);
 
     let mut current_range: ValueRange = (*in_range).clone();
 
@@ -89,10 +84,13 @@ fn sample_uniform(in_range: &ValueRange, seed_coins: &[u8; 32]) -> f64 {
     let mut bit_counter: usize = 0;
     while current_range.size() > 1_f64 {
 
-        let mid: f64 = (current_range.start + current_range.end).div_euclid(2_f64); 
+        // div_euclid by 2 floors the middle, negative values included, so that
+        // start <= mid < end and both halves are never empty. Halving the
+        // difference rather than the sum keeps it exact for bounds near 2^53.
+        let mid: f64 = current_range.start + div_euclid(current_range.end - current_range.start, 2_f64);
 
-        // Check if bit_counter exceeds seed_coins length (32)
-        if bit_counter > 31 {
+        // Check if bit_counter exceeds seed_coins length (128)
+        if bit_counter > 127 {
             panic!("Not enough coins.");
         }
 
@@ -112,100 +110,320 @@ fn sample_uniform(in_range: &ValueRange, seed_coins: &[u8; 32]) -> f64 {
     current_range.start
 }
 
+pub fn sample_hgd_i128(in_bounds: (i128, i128), out_bounds: (i128, i128), nsample: i128, seed_coins: &[u8; 128], refill: Option<&mut dyn FnMut() -> [u8; 128]>) -> i128 {
 
-#[cfg(test)]
-mod tests {
+    // Same as sample_hgd with integer bounds, for out ranges too large to be
+    // exact f64 values. HGD still works on f64 values, so the sizes given to
+    // it are rounded beyond 2^53, but the resulting sample is exact.
 
-    use super::ValueRange;
-    use super::sample_hgd;
-    use super::sample_uniform;
+    debug_assert!(are_binary(seed_coins), "Coins must be binary units. Found {:?}", seed_coins);
 
-    mod test_value_range {
+    let (in_start, in_end): (i128, i128) = in_bounds;
+    let (out_start, out_end): (i128, i128) = out_bounds;
+    let in_size: i128 = in_end - in_start + 1;
+    let out_size: i128 = out_end - out_start + 1;
 
-        use super::ValueRange;
+    if in_size < 1 {
+        panic!("in_range must have a positive size. Current size is : {:?}", in_size);
+    }
 
-        fn create_value_range (start: f64, end: f64) -> ValueRange {
-            ValueRange::new(start, end)
-        }
+    if out_size < 1 {
+        panic!("out_range must have a positive size. Current size is : {:?}", out_size);
+    }
 
-        #[test]
-        fn test_print_debug () {
-            let range: ValueRange = create_value_range(0.0_f64, 100.0_f64);
-            assert_eq!(format!("{:?}", range), "ValueRange { start: 0.0, end: 100.0 }");
-        }
+    if nsample < out_start || nsample > out_end {
+        panic!("nsample must be in out_range. Current nsample is {:?}, current out_range is {:?}.", nsample, out_bounds);
+    }
+
+    let nsample_index: i128 = nsample - out_start + 1;
+    if in_size == out_size {
+        return in_start + nsample_index - 1;
+    }
 
-        #[test]
-        fn test_equal () {
-            let range_1: ValueRange = create_value_range(0.0_f64, 100.0_f64);
-            let range_2: ValueRange = create_value_range(0.0_f64, 100.0_f64);
-            assert_eq!(range_1, range_2);
+    let mut prng: PRNG = PRNG::new(*seed_coins, refill);
+    let in_sample_num: i128 = HGD::rhyper(&(nsample_index as f64), &(in_size as f64), &((out_size - in_size) as f64), &mut prng) as i128;
 
-            let range_3: ValueRange = create_value_range(1.0_f64, 100.0_f64);
-            assert!(range_1 != range_3);
-        }
+    // Beyond 2^53 the rounded sizes may give a sample no exact urn could,
+    // which is brought back within the possible ones
+    let in_sample_num: i128 = in_sample_num.min(nsample_index).min(in_size).max(nsample_index - (out_size - in_size)).max(0);
+
+    if in_sample_num == 0 {
+        in_start
+    } else {
+        in_start + in_sample_num - 1
+    }
+}
 
-        #[test]
-        fn test_size () {
-            let range: ValueRange = create_value_range(0.0_f64, 100.0_f64);
-            assert_eq!(range.size(), 101.0);
+pub fn sample_uniform_i128(start: i128, end: i128, seed_coins: &[u8; 128]) -> i128 {
 
-            let range: ValueRange = create_value_range(100.0_f64, 100.0_f64);
-            assert_eq!(range.size(), 1.0);
+    // Same as sample_uniform with integer bounds. 128 coins are enough for
+    // any range of i128 values.
+
+    debug_assert!(are_binary(seed_coins), "Coins must be binary units. Found {:?}", seed_coins);
+
+    if start > end {
+        panic!("Provided range has zero size. Current range ({:?}, {:?})", start, end);
+    }
+
+    let (mut start, mut end): (i128, i128) = (start, end);
+    for bit in seed_coins.iter() {
+        if start == end {
+            break;
         }
 
-        #[test]
-        fn test_contains () {
-            let range: ValueRange = create_value_range(0.0_f64, 100.0_f64);
+        // Floors the middle like div_euclid, without overflowing
+        let mid: i128 = (start >> 1) + (end >> 1) + (start & end & 1);
 
-            assert_eq!(range.contains(&0.0_f64), true);
-            assert_eq!(range.contains(&100.0_f64), true);
-            assert_eq!(range.contains(&50.0_f64), true);
-            assert_eq!(range.contains(&101.0_f64), false);
-            assert_eq!(range.contains(&-1.0_f64), false);
+        match bit {
+            0 => end = mid,
+            1 => start = mid + 1,
+            _ => panic!("Coins must be binary units. Found {:?}", bit),
         }
     }
 
+    if start != end {
+        panic!("Not enough coins.");
+    }
+
+    start
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::ValueRange;
+    use super::sample_hgd;
+    use super::sample_hgd_i128;
+    use super::sample_uniform;
+    use super::sample_uniform_i128;
+
+    use crate::testing::{pseudo_random_bounds, pseudo_random_tape, repeat_word};
+
     #[test]
     fn test_sample_hgd () {
 
         let mut in_range: ValueRange = ValueRange::new(1_f64, 100_f64);
         let mut out_range: ValueRange = ValueRange::new(1_f64, 300_f64);
         let mut seed_coins: [u8; 32] = [1; 32];
+        let mut tape: [u8; 128] = repeat_word(&seed_coins);
 
-        assert_eq!(sample_hgd(&in_range, &out_range, &10_f64, &seed_coins), 10_f64);
-        assert_eq!(sample_hgd(&in_range, &out_range, &2_f64, &seed_coins), 2_f64);
+        assert_eq!(sample_hgd(&in_range, &out_range, &10_f64, &tape, Some(&mut || tape)), 10_f64);
+        assert_eq!(sample_hgd(&in_range, &out_range, &2_f64, &tape, Some(&mut || tape)), 2_f64);
 
         seed_coins = [0; 32];
         seed_coins[31] = 1;
+        tape = repeat_word(&seed_coins);
 
-        assert_eq!(sample_hgd(&in_range, &out_range, &10_f64, &seed_coins), 1_f64);
-        assert_eq!(sample_hgd(&in_range, &out_range, &8_f64, &seed_coins), 1_f64);
+        assert_eq!(sample_hgd(&in_range, &out_range, &10_f64, &tape, Some(&mut || tape)), 1_f64);
+        assert_eq!(sample_hgd(&in_range, &out_range, &8_f64, &tape, Some(&mut || tape)), 1_f64);
 
         in_range = ValueRange::new(-1_000_f64, 100_000_f64);
         out_range = ValueRange::new(-100_000_f64, 1_000_000_f64);
-        
+
         seed_coins = [0; 32];
         seed_coins[0] = 1_u8;
         seed_coins[2] = 1_u8;
         seed_coins[3] = 1_u8;
+        tape = repeat_word(&seed_coins);
+
+        assert_eq!(sample_hgd(&in_range, &out_range, &2000_f64, &tape, Some(&mut || tape)), 8406_f64);
+    }
 
-        assert_eq!(sample_hgd(&in_range, &out_range, &2000_f64, &seed_coins), 8406_f64);
+    #[test]
+    #[should_panic(expected = "Not enough coins.")]
+    fn test_sample_hgd_without_refill () {
+        let in_range: ValueRange = ValueRange::new(1_f64, 100_f64);
+        let out_range: ValueRange = ValueRange::new(1_f64, 300_f64);
+
+        // Sampling 10 values draws up to 10 times, more than one tape holds
+        sample_hgd(&in_range, &out_range, &10_f64, &[1; 128], None);
+    }
+
+    fn all_coin_patterns (bits: usize) -> Vec<[u8; 128]> {
+        // Every combination of the first bits coins, remaining coins being 0
+        (0..(1_usize << bits)).map(|pattern| {
+            let mut coins: [u8; 128] = [0; 128];
+            for (index, coin) in coins.iter_mut().take(bits).enumerate() {
+                *coin = ((pattern >> index) & 1) as u8;
+            }
+            coins
+        }).collect()
+    }
+
+    #[test]
+    fn test_sample_hgd_within_in_range () {
+        // Small and large samples, so that both hyp and HRUA* are used, over
+        // ranges of various signs and ratios
+        let ranges: [(f64, f64, f64, f64); 6] = [
+            (1_f64, 100_f64, 1_f64, 300_f64),
+            (-50_f64, 50_f64, -1000_f64, 1000_f64),
+            (0_f64, 1_f64, 0_f64, 1_000_000_f64),
+            (-1_000_000_f64, -999_000_f64, -2_000_000_f64, 0_f64),
+            (0_f64, 999_f64, 0_f64, 1000_f64),
+            (0_f64, 32767_f64, 0_f64, 2147483647_f64),
+        ];
+        let mut seed: u64 = 42;
+
+        for (in_start, in_end, out_start, out_end) in ranges.iter() {
+            let in_range: ValueRange = ValueRange::new(*in_start, *in_end);
+            let out_range: ValueRange = ValueRange::new(*out_start, *out_end);
+
+            for _ in 0..200 {
+                let (nsample, _) = pseudo_random_bounds(&mut seed, *out_start as i64, *out_end as i64);
+                let tape: [u8; 128] = pseudo_random_tape(&mut seed);
+                let mut tape_seed: u64 = seed;
+                let mut refill = || pseudo_random_tape(&mut tape_seed);
+
+                let sample: f64 = sample_hgd(&in_range, &out_range, &nsample, &tape, Some(&mut refill));
+                assert!(in_range.contains(&sample), "{} is not in {:?} for nsample {}", sample, in_range, nsample);
+            }
+
+            // Both ends of out_range map to the ends of in_range. Refilling with
+            // the same tape over and over could reject every draw of HRUA*.
+            let tape: [u8; 128] = pseudo_random_tape(&mut seed);
+            let mut refill = || pseudo_random_tape(&mut seed);
+            assert_eq!(sample_hgd(&in_range, &out_range, out_start, &tape, Some(&mut refill)), *in_start);
+            assert_eq!(sample_hgd(&in_range, &out_range, out_end, &tape, Some(&mut refill)), *in_end);
+        }
+    }
+
+    #[test]
+    fn test_sample_uniform_negative_ranges () {
+        // Ranges of 1000 values or less need no more than 10 coins
+        let patterns: Vec<[u8; 128]> = all_coin_patterns(10);
+        let mut seed: u64 = 42;
+
+        for _ in 0..50 {
+            let (negative_start, negative_end) = pseudo_random_bounds(&mut seed, -1000, -1);
+            let (mixed_start, _) = pseudo_random_bounds(&mut seed, -500, -1);
+            let (_, mixed_end) = pseudo_random_bounds(&mut seed, 0, 499);
+
+            for range in [ValueRange::new(negative_start, negative_end), ValueRange::new(mixed_start, mixed_end)].iter() {
+                let mut counts: Vec<u32> = vec![0; range.size() as usize];
+
+                for coins in patterns.iter() {
+                    let sample: f64 = sample_uniform(range, coins);
+                    assert!(range.contains(&sample), "{} is not in {:?}", sample, range);
+                    counts[(sample - range.start) as usize] += 1;
+                }
+
+                // Every value of the range can be reached
+                assert!(counts.iter().all(|count| *count > 0), "Unreachable value in {:?}", range);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sample_uniform_distribution () {
+        // Sizes which are powers of 2 are split evenly at each step, so that
+        // each value is given by the same number of coin patterns
+        let patterns: Vec<[u8; 128]> = all_coin_patterns(8);
+
+        for range in [ValueRange::new(-1000_f64, -745_f64), ValueRange::new(-128_f64, 127_f64), ValueRange::new(-17_f64, -2_f64)].iter() {
+            let mut counts: Vec<u32> = vec![0; range.size() as usize];
+            for coins in patterns.iter() {
+                counts[(sample_uniform(range, coins) - range.start) as usize] += 1;
+            }
+
+            let expected: u32 = patterns.len() as u32 / range.size() as u32;
+            assert!(counts.iter().all(|count| *count == expected), "Non uniform counts {:?} for {:?}", counts, range);
+        }
     }
 
     #[test]
     fn test_sample_uniform () {
 
         let mut in_range: ValueRange = ValueRange::new(1_f64, 1000_f64);
-        let mut seed_coins: [u8; 32] = [1; 32];
+        let mut seed_coins: [u8; 128] = [1; 128];
 
         assert_eq!(sample_uniform(&in_range, &seed_coins), 1000_f64);
 
         in_range = ValueRange::new(-1000_f64, 100_000_f64);
-        seed_coins = [0; 32];
+        seed_coins = [0; 128];
         seed_coins[0] = 1_u8;
         seed_coins[2] = 1_u8;
         seed_coins[3] = 1_u8;
 
         assert_eq!(sample_uniform(&in_range, &seed_coins), 68439_f64);
     }
+
+    #[test]
+    fn test_sample_uniform_beyond_32_coins () {
+        // Ranges of more than 2^32 values need more than 32 coins
+        let in_range: ValueRange = ValueRange::new(0_f64, 2_f64.powi(50) - 1_f64);
+
+        assert_eq!(sample_uniform(&in_range, &[1; 128]), 2_f64.powi(50) - 1_f64);
+        assert_eq!(sample_uniform(&in_range, &[0; 128]), 0_f64);
+
+        // The last of the 50 coins picks between the two values of the last step
+        let mut seed_coins: [u8; 128] = [0; 128];
+        seed_coins[49] = 1_u8;
+        assert_eq!(sample_uniform(&in_range, &seed_coins), 1_f64);
+    }
+
+    #[test]
+    #[should_panic(expected = "ValueRange : size should not be greater than 2^53.")]
+    fn test_sample_uniform_too_large () {
+        // Sizes are exact up to 2^53 values, far below what 128 coins can bisect
+        sample_uniform(&ValueRange::new(0_f64, 2_f64.powi(130)), &[0; 128]);
+    }
+
+    #[test]
+    fn test_sample_uniform_large_bounds () {
+        // Bounds around 2^52, whose sum is not an exact f64 : 3 coins must
+        // still split 8 values evenly, reaching each of them exactly once
+        for start in [2_f64.powi(52) - 4_f64, 2_f64.powi(52) + 1_f64, 2_f64.powi(53) - 8_f64].iter() {
+            let range: ValueRange = ValueRange::new(*start, start + 7_f64);
+            let mut samples: Vec<f64> = all_coin_patterns(3).iter().map(|coins| sample_uniform(&range, coins)).collect();
+            samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            assert_eq!(samples, (0..8).map(|offset| start + offset as f64).collect::<Vec<f64>>(), "{:?}", range);
+        }
+    }
+
+    #[test]
+    fn test_sample_i128 () {
+        // Same samples as the f64 versions on f64 ranges
+        let mut seed: u64 = 7;
+        for _ in 0..100 {
+            let (start, end) = pseudo_random_bounds(&mut seed, -1_000_000, 1_000_000);
+            let coins: [u8; 128] = pseudo_random_tape(&mut seed);
+            assert_eq!(sample_uniform_i128(start as i128, end as i128, &coins), sample_uniform(&ValueRange::new(start, end), &coins) as i128);
+
+            let in_range: ValueRange = ValueRange::new(start, (start + end).div_euclid(2_f64));
+            let out_range: ValueRange = ValueRange::new(start, end);
+            let nsample: f64 = (start + end).div_euclid(2_f64);
+            let mut tape_seed: u64 = seed;
+            let mut refill = || pseudo_random_tape(&mut tape_seed);
+            let expected: f64 = sample_hgd(&in_range, &out_range, &nsample, &coins, Some(&mut refill));
+            let mut tape_seed: u64 = seed;
+            let mut refill = || pseudo_random_tape(&mut tape_seed);
+            assert_eq!(sample_hgd_i128((in_range.start as i128, in_range.end as i128), (start as i128, end as i128), nsample as i128, &coins, Some(&mut refill)), expected as i128);
+        }
+
+        // Down to the last of the 50 coins needed for ranges near 2^50
+        for _ in 0..100 {
+            let (start, end) = pseudo_random_bounds(&mut seed, -(1 << 30), 1 << 30);
+            let (start, end): (f64, f64) = (start * 2_f64.powi(20), end * 2_f64.powi(20) + 12_345_f64);
+            let coins: [u8; 128] = pseudo_random_tape(&mut seed);
+            assert_eq!(sample_uniform_i128(start as i128, end as i128, &coins), sample_uniform(&ValueRange::new(start, end), &coins) as i128);
+        }
+
+        // Beyond 2^64, coins are still taken in order, the first one choosing
+        // the upper half of the range : coins spelling a value in binary
+        // sample it from [0, 2^100)
+        let value: i128 = (0xdead_beef_i128 << 68) | 0x1234_5678_9abc_def0_i128;
+        let mut coins: [u8; 128] = [0; 128];
+        for (index, coin) in coins.iter_mut().take(100).enumerate() {
+            *coin = ((value >> (99 - index)) & 1) as u8;
+        }
+        assert_eq!(sample_uniform_i128(0, (1 << 100) - 1, &coins), value);
+        assert_eq!(sample_uniform_i128(1 << 80, (1 << 80) + (1 << 100) - 1, &coins), (1 << 80) + value);
+
+        // And 128 coins are enough for the whole i128 range
+        let coins: [u8; 128] = [1; 128];
+        assert_eq!(sample_uniform_i128(i128::MIN, i128::MAX, &coins), i128::MAX);
+        assert_eq!(sample_uniform_i128(i128::MIN, i128::MAX, &[0; 128]), i128::MIN);
+    }
 }
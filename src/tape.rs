@@ -0,0 +1,303 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+
+use aes::Aes256;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hmac::{Hmac, Mac};
+use sha2::{Sha256, Sha512};
+
+use super::key::wipe_value;
+use super::util::get_bits_list;
+
+// Coins of a tape, sampling a ciphertext uniformly consumes one per bit of out_range size
+pub const COIN_BUDGET: u32 = 128;
+
+type HmacSha256 = Hmac<Sha256>;
+type HmacSha512 = Hmac<Sha512>;
+type Aes256Ctr = ctr::Ctr128BE<Aes256>;
+
+pub trait TapeGenerator {
+    // Source of the coins drawn for a value. A tape is a keyed stream of
+    // coins seeded with an encoding of the value, of which next_coins gives
+    // the following 128 coins each time it is called.
+    //
+    // The key is first set up into a Context, which an OPE keeps so that
+    // building each tape only has to feed it the value.
+    type Tape;
+    type Context;
+
+    fn context (&self, key: &[u8]) -> Self::Context;
+
+    fn tape_from_context (&self, context: &Self::Context, input: &[u8]) -> Self::Tape;
+
+    fn tape (&self, key: &[u8], input: &[u8]) -> Self::Tape {
+        // The context only lives for this tape, and is wiped afterwards
+        let context: TapeContext<Self::Context> = TapeContext(self.context(key));
+        self.tape_from_context(&context.0, input)
+    }
+
+    fn next_coins (tape: &mut Self::Tape) -> [u8; 128];
+
+    fn coins (&self, key: &[u8], value: f64) -> [u8; 128] {
+        // First coins of the tape of the given value
+        Self::next_coins(&mut self.tape(key, &tape_input(&value)))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HmacSha256AesCtr {
+    iv: [u8; 16],
+}
+
+impl HmacSha256AesCtr {
+    pub fn with_iv (iv: [u8; 16]) -> HmacSha256AesCtr {
+        // Keystreams are seeded per value, so the default zero IV is as good
+        // as any other. Another one only helps matching other implementations.
+        HmacSha256AesCtr { iv }
+    }
+}
+
+impl TapeGenerator for HmacSha256AesCtr {
+    // Default generator. A seed is derived from the value with HMAC-SHA256
+    // keyed with the encryption key, then used as an AES-256 key in CTR mode
+    // (zero IV unless given another one). Coins are the bits of the encrypted
+    // zero blocks.
+    type Tape = Aes256Ctr;
+    type Context = HmacSha256;
+
+    fn context (&self, key: &[u8]) -> HmacSha256 {
+        HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any size")
+    }
+
+    fn tape_from_context (&self, context: &HmacSha256, input: &[u8]) -> Aes256Ctr {
+        // A copy of the keyed HMAC, which already went through the key. It
+        // is wiped once finalized, as the context itself, see
+        // hmac_sha256_wiped.
+        let mut hmac: HmacSha256 = context.clone();
+        hmac.update(input);
+
+        let seed = hmac.clone().finalize().into_bytes();
+        wipe_value(&mut hmac);
+
+        Aes256Ctr::new(&seed, &self.iv.into())
+    }
+
+    fn next_coins (tape: &mut Aes256Ctr) -> [u8; 128] {
+        aes_ctr_coins(tape)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HmacSha512AesCtr;
+
+impl TapeGenerator for HmacSha512AesCtr {
+    // Same construction with HMAC-SHA512, for policies ruling out SHA-256.
+    // The first 32 bytes of the seed are the AES-256 key and the next 16
+    // bytes the IV, so tapes have nothing in common with the default ones.
+    type Tape = Aes256Ctr;
+    type Context = HmacSha512;
+
+    fn context (&self, key: &[u8]) -> HmacSha512 {
+        HmacSha512::new_from_slice(key).expect("HMAC accepts keys of any size")
+    }
+
+    fn tape_from_context (&self, context: &HmacSha512, input: &[u8]) -> Aes256Ctr {
+        let mut hmac: HmacSha512 = context.clone();
+        hmac.update(input);
+
+        let seed = hmac.clone().finalize().into_bytes();
+        wipe_value(&mut hmac);
+
+        Aes256Ctr::new(seed[..32].into(), seed[32..48].into())
+    }
+
+    fn next_coins (tape: &mut Aes256Ctr) -> [u8; 128] {
+        aes_ctr_coins(tape)
+    }
+}
+
+pub struct TapeContext<C>(pub C);
+
+impl<C> Drop for TapeContext<C> {
+    fn drop (&mut self) {
+        // Keyed HMACs are as good as the key, so they are wiped as the key
+        // is. Contexts owning memory elsewhere are left to their own Drop.
+        wipe_value(&mut self.0);
+    }
+}
+
+impl<C> fmt::Debug for TapeContext<C> {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Keyed with the encryption key, so never printed either
+        f.write_str("<redacted>")
+    }
+}
+
+fn aes_ctr_coins (tape: &mut Aes256Ctr) -> [u8; 128] {
+    // Encrypt the next zero block of the keystream and expand it into coins
+    let mut output: [u8; 16] = [0; 16];
+    tape.apply_keystream(&mut output);
+
+    get_bits_list(&output)
+}
+
+#[cfg(not(feature = "decimal-tape"))]
+pub fn tape_input (data: &f64) -> Vec<u8> {
+    // Encode the value on 16 big-endian bytes, whatever its magnitude.
+    //
+    // A decimal representation would have a length depending on the value,
+    // and so would the time spent hashing it, leaking information about the
+    // partition points visited during encryption. Values are integers, and
+    // any value beyond the i128 range would not fit in a 128 coins tape.
+    (*data as i128).to_be_bytes().to_vec()
+}
+
+#[cfg(feature = "decimal-tape")]
+pub fn tape_input (data: &f64) -> Vec<u8> {
+    // Legacy encoding of the value as a decimal string, kept for ciphertexts
+    // produced before the fixed-width encoding. Its length depends on the
+    // value, see the default tape_input.
+    decimal_tape_input(data)
+}
+
+#[cfg(not(feature = "decimal-tape"))]
+pub fn wide_tape_input (data: i128) -> Vec<u8> {
+    // Same as tape_input for integers beyond the exact f64 values, so that
+    // both give the same bytes whenever data is an exact f64
+    data.to_be_bytes().to_vec()
+}
+
+#[cfg(feature = "decimal-tape")]
+pub fn wide_tape_input (data: i128) -> Vec<u8> {
+    decimal_wide_tape_input(data)
+}
+
+pub fn decimal_wide_tape_input (data: i128) -> Vec<u8> {
+    // Same digits as decimal_tape_input for integers below 2^53. Beyond,
+    // displaying an f64 only keeps the digits needed to tell it apart from
+    // other f64 values, where this gives every digit, as pyope's str() does.
+    data.to_string().into_bytes()
+}
+
+pub fn decimal_tape_input (data: &f64) -> Vec<u8> {
+    // Encode the value as its decimal string, the way pyope encodes Python
+    // integers with str(). Displaying an f64 never uses an exponent, and
+    // adding 0 turns -0 into 0, which Python integers cannot represent.
+    (*data + 0_f64).to_string().into_bytes()
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::Aes256Ctr;
+    use super::HmacSha256;
+    use super::HmacSha256AesCtr;
+    use super::HmacSha512;
+    use super::HmacSha512AesCtr;
+    use super::TapeContext;
+    use super::TapeGenerator;
+    use super::aes_ctr_coins;
+
+    use core::mem::{ManuallyDrop, needs_drop};
+
+    use ctr::cipher::KeyIvInit;
+    use hmac::Mac;
+    use super::decimal_tape_input;
+    use super::decimal_wide_tape_input;
+    use super::tape_input;
+    use super::wide_tape_input;
+
+    #[test]
+    #[cfg(not(feature = "decimal-tape"))]
+    fn test_tape_input () {
+        // Values of any decimal length are fed to the HMAC on 16 bytes
+        for value in [0_f64, 1_f64, -1_f64, 1_000_f64, 123_456_789_f64, -1e30_f64].iter() {
+            assert_eq!(tape_input(value).len(), 16);
+        }
+
+        assert_eq!(tape_input(&1_f64), [vec![0; 15], vec![1]].concat());
+        assert_eq!(tape_input(&-1_f64), vec![255; 16]);
+        assert_eq!(tape_input(&0_f64), tape_input(&-0_f64));
+    }
+
+    #[test]
+    #[cfg(feature = "decimal-tape")]
+    fn test_tape_input () {
+        assert_eq!(tape_input(&1_f64), b"1".to_vec());
+        assert_eq!(tape_input(&-1_000_f64), b"-1000".to_vec());
+    }
+
+    #[test]
+    fn test_decimal_tape_input () {
+        assert_eq!(decimal_tape_input(&0_f64), b"0".to_vec());
+        assert_eq!(decimal_tape_input(&-0_f64), b"0".to_vec());
+        assert_eq!(decimal_tape_input(&-42_f64), b"-42".to_vec());
+        assert_eq!(decimal_tape_input(&1e21_f64), b"1000000000000000000000".to_vec());
+    }
+
+    #[test]
+    fn test_wide_tape_input () {
+        for value in [0_f64, 1_f64, -1_f64, 1_000_f64, 2_f64.powi(53), -2_f64.powi(53)].iter() {
+            assert_eq!(wide_tape_input(*value as i128), tape_input(value));
+            assert_eq!(decimal_wide_tape_input(*value as i128), decimal_tape_input(value));
+        }
+
+        assert_eq!(decimal_wide_tape_input(1_i128 << 120), b"1329227995784915872903807060280344576".to_vec());
+        assert_eq!(decimal_tape_input(&2_f64.powi(120)), b"1329227995784916000000000000000000000".to_vec());
+    }
+
+    #[test]
+    fn test_tape_from_context () {
+        let generator: HmacSha256AesCtr = HmacSha256AesCtr::default();
+        let context: HmacSha256 = generator.context(b"key");
+
+        for value in [0_f64, 1_f64, -1_f64, 1_000_f64, 123_456_789_f64].iter() {
+            // Tape built from scratch, keying a new HMAC for the value
+            let mut hmac = HmacSha256::new_from_slice(b"key").unwrap();
+            hmac.update(&tape_input(value));
+            let mut fresh: Aes256Ctr = Aes256Ctr::new(&hmac.finalize().into_bytes(), &[0_u8; 16].into());
+
+            // A same context is reused for every value
+            let mut tape: Aes256Ctr = generator.tape_from_context(&context, &tape_input(value));
+            for _ in 0..3 {
+                assert_eq!(HmacSha256AesCtr::next_coins(&mut tape), aes_ctr_coins(&mut fresh));
+            }
+            assert_eq!(generator.coins(b"key", *value), HmacSha256AesCtr::next_coins(&mut generator.tape_from_context(&context, &tape_input(value))));
+        }
+
+        let other: HmacSha512AesCtr = HmacSha512AesCtr;
+        assert_eq!(HmacSha512AesCtr::next_coins(&mut other.tape_from_context(&other.context(b"key"), &tape_input(&1_f64))), other.coins(b"key", 1_f64));
+    }
+
+    #[test]
+    fn test_hmac_sha512_aes_ctr () {
+        let generator: HmacSha512AesCtr = HmacSha512AesCtr;
+
+        // Same key and value always give the same coins, tape after tape
+        assert_eq!(generator.coins(b"key", 1_f64), generator.coins(b"key", 1_f64));
+
+        let mut tape = generator.tape(b"key", &tape_input(&1_f64));
+        let mut other = generator.tape(b"key", &tape_input(&1_f64));
+        for _ in 0..10 {
+            assert_eq!(HmacSha512AesCtr::next_coins(&mut tape), HmacSha512AesCtr::next_coins(&mut other));
+        }
+
+        // And anything else gives other coins
+        assert_ne!(generator.coins(b"key", 1_f64), generator.coins(b"key", 2_f64));
+        assert_ne!(generator.coins(b"key", 1_f64), generator.coins(b"other key", 1_f64));
+        assert_ne!(generator.coins(b"key", 1_f64), HmacSha256AesCtr::default().coins(b"key", 1_f64));
+    }
+
+    #[test]
+    fn test_tape_context_wiped () {
+        // Both HMACs are plain bytes, which TapeContext wipes when dropped
+        assert!(!needs_drop::<HmacSha256>());
+        assert!(!needs_drop::<HmacSha512>());
+
+        let mut context: ManuallyDrop<TapeContext<[u8; 64]>> = ManuallyDrop::new(TapeContext([0x5c; 64]));
+        unsafe { ManuallyDrop::drop(&mut context) };
+        assert_eq!(context.0, [0; 64]);
+    }
+}
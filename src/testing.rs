@@ -0,0 +1,36 @@
+// Helpers shared by the unit tests of several modules. Nothing here is
+// meant to be random in any cryptographic sense : the generator is a plain
+// linear congruential one, from fixed seeds, so that failing cases replay.
+
+pub(crate) fn lcg_next (seed: &mut u64) -> u64 {
+    // Next state of the generator, which is also the value drawn
+    *seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+    *seed
+}
+
+pub(crate) fn pseudo_random_bounds (seed: &mut u64, min: i64, max: i64) -> (f64, f64) {
+    // Two values from min to max, both included, in increasing order.
+    // Enough to sweep ranges in tests.
+    let mut next = || min + ((lcg_next(seed) >> 33) % ((max - min + 1) as u64)) as i64;
+
+    let (a, b) = (next(), next());
+    (a.min(b) as f64, a.max(b) as f64)
+}
+
+pub(crate) fn pseudo_random_tape (seed: &mut u64) -> [u8; 128] {
+    // Tape of coins, each being the top bit of a draw
+    let mut coins: [u8; 128] = [0; 128];
+    for coin in coins.iter_mut() {
+        *coin = (lcg_next(seed) >> 63) as u8;
+    }
+    coins
+}
+
+pub(crate) fn repeat_word (word: &[u8; 32]) -> [u8; 128] {
+    // Build a tape repeating the given word, so that every draw is equal
+    let mut coins: [u8; 128] = [0; 128];
+    for chunk in coins.chunks_mut(32) {
+        chunk.copy_from_slice(word);
+    }
+    coins
+}
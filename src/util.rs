@@ -0,0 +1,165 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum HexError {
+    // Reasons for which a string cannot be read back into coins
+    InvalidLength(usize),
+    InvalidDigit(char),
+}
+
+impl fmt::Display for HexError {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HexError::InvalidLength(len) => write!(f, "OPE : coins should be written with 32 hexadecimal digits. Found {}", len),
+            HexError::InvalidDigit(digit) => write!(f, "OPE : invalid hexadecimal digit {:?}.", digit),
+        }
+    }
+}
+
+impl Error for HexError {}
+
+pub fn bits_of(bytes: &[u8]) -> Vec<u8> {
+
+    // Expand the given bytes into a list of bits (one u8 per bit), most
+    // significant bit first, for tapes of any length.
+
+    bytes.iter().flat_map(|byte| (0..8).map(move |offset| (byte >> (7 - offset)) & 1_u8)).collect()
+}
+
+pub fn get_bits_list(bytes: &[u8; 16]) -> [u8; 128] {
+
+    // Same as bits_of, for the 16 bytes of a tape of 128 coins
+
+    let mut bits: [u8; 128] = [0; 128];
+    bits.copy_from_slice(&bits_of(bytes));
+
+    bits
+}
+
+pub fn bits_to_hex(bits: &[u8; 128]) -> String {
+
+    // Write the 128 coins of a tape as 32 lowercase hexadecimal digits, 4
+    // coins per digit, most significant first as in get_bits_list.
+
+    debug_assert!(are_binary(bits), "Coins must be binary units. Found {:?}", bits);
+
+    bits.chunks(4).map(|nibble| {
+        let digit: u32 = nibble.iter().fold(0_u32, |digit, bit| (digit << 1) | *bit as u32);
+        core::char::from_digit(digit, 16).expect("4 coins make a hexadecimal digit")
+    }).collect()
+}
+
+pub fn hex_to_bits(hex: &str) -> Result<[u8; 128], HexError> {
+
+    // Read back coins written by bits_to_hex, in either case
+
+    let len: usize = hex.chars().count();
+    if len != 32 {
+        return Err(HexError::InvalidLength(len));
+    }
+
+    let mut bits: [u8; 128] = [0; 128];
+
+    for (index, character) in hex.chars().enumerate() {
+        let digit: u32 = character.to_digit(16).ok_or(HexError::InvalidDigit(character))?;
+        for offset in 0..4 {
+            bits[index * 4 + offset] = ((digit >> (3 - offset)) & 1) as u8;
+        }
+    }
+
+    Ok(bits)
+}
+
+pub fn are_binary(coins: &[u8]) -> bool {
+    coins.iter().all(|coin| *coin <= 1_u8)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::HexError;
+    use super::are_binary;
+    use super::bits_of;
+    use super::bits_to_hex;
+    use super::get_bits_list;
+    use super::hex_to_bits;
+
+    #[test]
+    fn test_get_bits_list () {
+        assert_eq!(get_bits_list(&[0; 16]).to_vec(), vec![0; 128]);
+        assert_eq!(get_bits_list(&[255; 16]).to_vec(), vec![1; 128]);
+
+        let mut bytes: [u8; 16] = [0; 16];
+        bytes[0] = 128;
+        bytes[15] = 5;
+
+        let bits: [u8; 128] = get_bits_list(&bytes);
+        assert_eq!(bits[0], 1);
+        assert_eq!(bits[1..125].iter().filter(|bit| **bit != 0).count(), 0);
+        assert_eq!(bits[125..].to_vec(), vec![1, 0, 1]);
+    }
+
+    #[test]
+    fn test_bits_of () {
+        assert_eq!(bits_of(&[]), Vec::<u8>::new());
+        assert_eq!(bits_of(&[0b1010_0110]), vec![1, 0, 1, 0, 0, 1, 1, 0]);
+
+        let mut bytes: [u8; 16] = [0; 16];
+        bytes[0] = 128;
+        bytes[15] = 5;
+        assert_eq!(bits_of(&bytes).len(), 128);
+        assert_eq!(bits_of(&bytes), get_bits_list(&bytes).to_vec());
+
+        // 0x00, 0x01, ..., 0x1f
+        let bytes: Vec<u8> = (0..32).collect();
+        let bits: Vec<u8> = bits_of(&bytes);
+        assert_eq!(bits.len(), 256);
+        assert_eq!(bits[..16].to_vec(), vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(bits[248..].to_vec(), vec![0, 0, 0, 1, 1, 1, 1, 1]);
+        assert_eq!(bits.iter().filter(|bit| **bit == 1).count(), 80);
+    }
+
+    #[test]
+    fn test_bits_to_hex () {
+        assert_eq!(bits_to_hex(&[0; 128]), "00000000000000000000000000000000");
+        assert_eq!(bits_to_hex(&[1; 128]), "ffffffffffffffffffffffffffffffff");
+
+        // Coins of 1 in test_tape_gen of the ope module
+        let coins: [u8; 128] = [
+            1, 0, 1, 0, 1, 1, 1, 1, 1, 1, 0, 1, 1, 0, 1, 1, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 0,
+            1, 1, 0, 0, 1, 0, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 0, 1, 0, 0, 0, 0, 1, 1, 0, 0, 1,
+            1, 0, 1, 1, 1, 1, 1, 0, 1, 1, 0, 0, 1, 1, 1, 0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 0, 1, 0, 1, 1, 0, 0,
+            0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 1, 0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 1, 1, 1, 0, 1, 0, 0, 0
+        ];
+        assert_eq!(bits_to_hex(&coins), "afdbc812c9c06a19becee62c242f10e8");
+        assert_eq!(hex_to_bits("afdbc812c9c06a19becee62c242f10e8"), Ok(coins));
+        assert_eq!(hex_to_bits("AFDBC812C9C06A19BECEE62C242F10E8"), Ok(coins));
+    }
+
+    #[test]
+    fn test_hex_to_bits () {
+        // Coins of test_tape_gen : 1 and 1000, then 1 with decimal-tape
+        for hex in ["00000000000000000000000000000000", "ffffffffffffffffffffffffffffffff", "afdbc812c9c06a19becee62c242f10e8", "09f0e7641663fb87589a1fd608d5d9fb", "be7d54a8021a41a1c62103ef8ced84ec"] {
+            assert_eq!(bits_to_hex(&hex_to_bits(hex).unwrap()), hex);
+        }
+
+        assert_eq!(hex_to_bits("00000000000000000000000000000001").unwrap()[127], 1);
+        assert_eq!(hex_to_bits("8"), Err(HexError::InvalidLength(1)));
+        assert_eq!(hex_to_bits(&"0".repeat(33)), Err(HexError::InvalidLength(33)));
+        assert_eq!(hex_to_bits("0000000000000000000000000000000g"), Err(HexError::InvalidDigit('g')));
+        assert_eq!(format!("{}", HexError::InvalidLength(1)), "OPE : coins should be written with 32 hexadecimal digits. Found 1");
+        assert_eq!(format!("{}", HexError::InvalidDigit('g')), "OPE : invalid hexadecimal digit 'g'.");
+    }
+
+    #[test]
+    fn test_are_binary () {
+        assert!(are_binary(&[0; 32]));
+        assert!(are_binary(&[1; 32]));
+        assert!(are_binary(&[0, 1, 1, 0]));
+        assert!(!are_binary(&[0, 1, 2, 0]));
+    }
+}
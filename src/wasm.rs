@@ -0,0 +1,92 @@
+// JavaScript bindings of OPE, for builds targeting wasm32-unknown-unknown
+// with wasm-bindgen (wasm-pack build --features wasm for instance).
+//
+// Keys are JavaScript strings and values are numbers, which are f64 values
+// on both sides. Errors are thrown as JavaScript errors. WebAssembly aborts
+// on panics, so everything which can be checked upfront is.
+
+use alloc::string::{String, ToString};
+
+use wasm_bindgen::prelude::*;
+
+use super::ope::OPE;
+use super::range::ValueRange;
+
+#[wasm_bindgen(js_name = Ope)]
+pub struct WasmOpe {
+    ope: OPE,
+}
+
+#[wasm_bindgen(js_class = Ope)]
+impl WasmOpe {
+    #[wasm_bindgen(constructor)]
+    pub fn new (key: &str, in_start: f64, in_end: f64, out_start: f64, out_end: f64) -> Result<WasmOpe, JsError> {
+        WasmOpe::try_new(key, in_start, in_end, out_start, out_end).map_err(|error| JsError::new(&error))
+    }
+
+    pub fn encrypt (&self, plaintext: f64) -> Result<f64, JsError> {
+        self.try_encrypt(plaintext).map_err(|error| JsError::new(&error))
+    }
+
+    pub fn decrypt (&self, ciphertext: f64) -> Result<f64, JsError> {
+        self.try_decrypt(ciphertext).map_err(|error| JsError::new(&error))
+    }
+}
+
+impl WasmOpe {
+    // JsError can only be built on wasm targets, so the bindings above only
+    // wrap these, which can be tested natively
+
+    fn try_new (key: &str, in_start: f64, in_end: f64, out_start: f64, out_end: f64) -> Result<WasmOpe, String> {
+        let in_range: ValueRange = ValueRange::try_new(in_start, in_end).map_err(|error| error.to_string())?;
+        let out_range: ValueRange = ValueRange::try_new(out_start, out_end).map_err(|error| error.to_string())?;
+
+        OPE::try_new(key, &in_range, &out_range).map(|ope| WasmOpe { ope }).map_err(|error| error.to_string())
+    }
+
+    fn try_encrypt (&self, plaintext: f64) -> Result<f64, String> {
+        self.ope.try_encrypt(&plaintext).map_err(|error| error.to_string())
+    }
+
+    fn try_decrypt (&self, ciphertext: f64) -> Result<f64, String> {
+        self.ope.try_decrypt(&ciphertext).map_err(|error| error.to_string())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::WasmOpe;
+
+    use crate::ope::OPE;
+    use crate::range::ValueRange;
+
+    #[test]
+    fn test_wasm_ope () {
+        let ope: WasmOpe = WasmOpe::try_new("key", 0_f64, 100_f64, 0_f64, 1000_f64).unwrap();
+        let expected: OPE = OPE::new("key", &ValueRange::new(0_f64, 100_f64), &ValueRange::new(0_f64, 1000_f64));
+
+        let ciphertext: f64 = ope.try_encrypt(42_f64).unwrap();
+        assert_eq!(ciphertext, expected.encrypt(&42_f64));
+        assert_eq!(ope.try_decrypt(ciphertext).unwrap(), 42_f64);
+    }
+
+    #[test]
+    fn test_wasm_ope_errors () {
+        assert_eq!(WasmOpe::try_new("key", 0_f64, 1000_f64, 0_f64, 100_f64).err().unwrap(), "OPE : in_range size (1001) should not be greater than out_range size (101).");
+        assert!(WasmOpe::try_new("key", 0.5_f64, 100_f64, 0_f64, 1000_f64).is_err());
+
+        let ope: WasmOpe = WasmOpe::try_new("key", 0_f64, 100_f64, 0_f64, 1000_f64).unwrap();
+        assert!(ope.try_encrypt(101_f64).is_err());
+        assert!(ope.try_encrypt(f64::NAN).is_err());
+        assert!(ope.try_decrypt(1001_f64).is_err());
+        assert!(ope.try_decrypt(0.5_f64).is_err());
+
+        // Integers of out_range matching no plaintext are errors too, a panic
+        // would abort the WebAssembly instance
+        let ciphertexts: Vec<f64> = (0..=100).map(|plaintext| ope.try_encrypt(plaintext as f64).unwrap()).collect();
+        let invalid: f64 = (0..=1000).map(|value| value as f64).find(|value| !ciphertexts.contains(value)).unwrap();
+        assert_eq!(ope.try_decrypt(invalid).err().unwrap(), format!("OPE : invalid ciphertext ({}), it does not match any plaintext.", invalid));
+    }
+}
@@ -0,0 +1,45 @@
+use rust_opse::{OPE, ValueRange};
+
+// Key, in_range, out_range, plaintext and ciphertext of each golden vector
+type GoldenVector = (String, ValueRange, ValueRange, f64, f64);
+
+fn golden_vectors () -> Vec<GoldenVector> {
+    include_str!("data/golden.tsv")
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            assert_eq!(fields.len(), 7, "Golden vectors have 7 fields. Found {:?}", line);
+
+            let numbers: Vec<f64> = fields[1..].iter().map(|field| field.parse::<f64>().unwrap()).collect();
+            (fields[0].to_string(), ValueRange::new(numbers[0], numbers[1]), ValueRange::new(numbers[2], numbers[3]), numbers[4], numbers[5])
+        })
+        .collect()
+}
+
+#[test]
+fn test_encrypt_deterministic () {
+    // Two OPEs built from the same key and ranges, as well as one OPE used
+    // twice, always give the same ciphertexts
+    for (key, in_range, out_range, plaintext, _) in golden_vectors().iter() {
+        let ope: OPE = OPE::new(key, in_range, out_range);
+        let other: OPE = OPE::new(key, in_range, out_range);
+
+        assert_eq!(ope.encrypt(plaintext), ope.encrypt(plaintext));
+        assert_eq!(ope.encrypt(plaintext), other.encrypt(plaintext));
+    }
+}
+
+#[test]
+#[cfg(not(feature = "decimal-tape"))]
+fn test_encrypt_golden_vectors () {
+    let vectors: Vec<GoldenVector> = golden_vectors();
+    assert_eq!(vectors.len(), 24);
+
+    for (key, in_range, out_range, plaintext, ciphertext) in vectors.iter() {
+        let ope: OPE = OPE::new(key, in_range, out_range);
+
+        assert_eq!(ope.encrypt(plaintext), *ciphertext, "key = {:?}, in_range = {:?}, out_range = {:?}, plaintext = {}", key, in_range, out_range, plaintext);
+        assert_eq!(ope.decrypt(ciphertext), *plaintext);
+    }
+}
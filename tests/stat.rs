@@ -0,0 +1,29 @@
+use rust_opse::{sample_hgd, sample_uniform, ValueRange};
+
+#[test]
+fn test_sample_hgd () {
+    let in_range: ValueRange = ValueRange::new(1_f64, 100_f64);
+    let out_range: ValueRange = ValueRange::new(1_f64, 300_f64);
+
+    let seed_coins: [u8; 128] = [1; 128];
+    assert_eq!(sample_hgd(&in_range, &out_range, &10_f64, &seed_coins, Some(&mut || [1; 128])), 10_f64);
+
+    // Equal sizes map nsample onto in_range one to one, without drawing any coin
+    let out_range: ValueRange = ValueRange::new(101_f64, 200_f64);
+    assert_eq!(sample_hgd(&in_range, &out_range, &150_f64, &seed_coins, None), 50_f64);
+}
+
+#[test]
+fn test_sample_uniform () {
+    let in_range: ValueRange = ValueRange::new(1_f64, 1000_f64);
+
+    assert_eq!(sample_uniform(&in_range, &[1; 128]), 1000_f64);
+    assert_eq!(sample_uniform(&in_range, &[0; 128]), 1_f64);
+}
+
+#[test]
+#[should_panic(expected = "Coins must be binary units")]
+fn test_sample_uniform_non_binary_coins () {
+    let in_range: ValueRange = ValueRange::new(1_f64, 1000_f64);
+    sample_uniform(&in_range, &[2; 128]);
+}